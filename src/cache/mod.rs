@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    model: String,
+    max_tokens: usize,
+    temperature: f32,
+    summary: String,
+}
+
+/// On-disk store of generated summaries, keyed by a hash of the model,
+/// token budget, temperature and the prompts sent: the system prompt and the
+/// user prompt with the document filled in.
+#[derive(Debug, Clone)]
+pub struct SummaryCache {
+    dir: PathBuf,
+    model: String,
+}
+
+impl SummaryCache {
+    pub fn new(dir: PathBuf, model: String) -> Self {
+        Self { dir, model }
+    }
+
+    pub fn default_dir() -> Result<PathBuf> {
+        dirs::cache_dir()
+            .map(|dir| dir.join("doctldr"))
+            .context("Could not find cache directory")
+    }
+
+    pub async fn get(&self, max_tokens: usize, temperature: f32, system: &str, prompt: &str) -> Option<String> {
+        let contents = fs::read_to_string(self.entry_path(max_tokens, temperature, system, prompt)).await.ok()?;
+        let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+
+        // Guard against hash collisions across models
+        if entry.model != self.model || entry.max_tokens != max_tokens || entry.temperature != temperature {
+            return None;
+        }
+        Some(entry.summary)
+    }
+
+    pub async fn put(&self, max_tokens: usize, temperature: f32, system: &str, prompt: &str, summary: &str) -> Result<()> {
+        let entry = CacheEntry {
+            model: self.model.clone(),
+            max_tokens,
+            temperature,
+            summary: summary.to_string(),
+        };
+
+        fs::create_dir_all(&self.dir).await?;
+        fs::write(self.entry_path(max_tokens, temperature, system, prompt), serde_json::to_string(&entry)?).await?;
+        Ok(())
+    }
+
+    pub async fn clear(dir: &Path) -> Result<()> {
+        if fs::try_exists(dir).await? {
+            fs::remove_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to clear cache at {}", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, max_tokens: usize, temperature: f32, system: &str, prompt: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.model.as_bytes());
+        hasher.update([0]);
+        hasher.update(max_tokens.to_le_bytes());
+        hasher.update([0]);
+        hasher.update(temperature.to_le_bytes());
+        hasher.update([0]);
+        hasher.update(system.as_bytes());
+        hasher.update([0]);
+        hasher.update(prompt.as_bytes());
+
+        let key: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}.json", key))
+    }
+}
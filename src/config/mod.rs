@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::processing::DocumentFormat;
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
@@ -9,6 +13,8 @@ pub enum ConfigError {
     ParseError(#[from] toml::de::Error),
     #[error("Environment variable not found: {0}")]
     EnvVarNotFound(String),
+    #[error("Invalid config value: {0}")]
+    InvalidValue(String),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,46 +29,439 @@ pub struct Config {
 pub struct DefaultConfig {
     pub model: String,
     pub max_tokens: usize,
+    /// Output format. Set by `--format`; when empty, `output.default_format`
+    /// applies. See [`Config::output_format`].
+    #[serde(default)]
     pub format: String,
     pub verbose: bool,
+    /// Sampling temperature, from 0.0 (deterministic) to 2.0.
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    /// Summaries shorter than this are requested again once, asking for
+    /// more detail. `0` disables the check.
+    #[serde(default)]
+    pub min_tokens: usize,
+    /// Hard limit on summary length, applied after generation.
+    #[serde(default)]
+    pub max_summary_chars: Option<usize>,
+    /// Request summaries again, up to `api.max_retries` times, when they
+    /// come back empty, as a refusal or apology, or implausibly short.
+    #[serde(default)]
+    pub self_check: bool,
+    /// Request an output budget proportional to each input's size, between
+    /// `adaptive_min_tokens` and `max_tokens`, instead of always `max_tokens`.
+    #[serde(default)]
+    pub adaptive_tokens: bool,
+    /// Smallest budget `adaptive_tokens` requests.
+    #[serde(default = "default_adaptive_min_tokens")]
+    pub adaptive_min_tokens: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiConfig {
     pub provider: String,
     pub key_env: String,
+    /// Overall limit for a single API request, including reading the response.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Limit for establishing the connection, so unreachable hosts fail fast.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Retries for timeouts, connection errors, and HTTP 429/500/502/503.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Base delay for exponential backoff between retries.
+    #[serde(default = "default_retry_base_ms")]
+    pub retry_base_ms: u64,
+    /// Client-side limit on API requests, retries included. Requests wait
+    /// for a slot rather than failing. Unlimited when unset.
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Proxy for every request, e.g. `http://proxy.corp:3128`. When unset,
+    /// the `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY`
+    /// environment variables apply.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Headers added to every API request, e.g. for an authenticating
+    /// gateway.
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, String>,
+    /// Root of the OpenAI-compatible API used by the openai provider.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Root of the Ollama server used by the ollama provider. Defaults to
+    /// `http://localhost:11434`.
+    #[serde(default)]
+    pub ollama_url: Option<String>,
+    /// Azure OpenAI resource URL, e.g. `https://my-resource.openai.azure.com`.
+    #[serde(default)]
+    pub azure_endpoint: Option<String>,
+    /// Azure OpenAI deployment name. Defaults to the model name.
+    #[serde(default)]
+    pub azure_deployment: Option<String>,
+    /// Azure OpenAI REST API version.
+    #[serde(default = "default_api_version")]
+    pub api_version: String,
+    /// Context window of the model in tokens, for models the built-in table
+    /// doesn't know or gets wrong.
+    #[serde(default)]
+    pub context_window: Option<usize>,
+    /// Replaces the built-in system prompt.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// System prompts for documents of one format, keyed by format name
+    /// (`markdown`, `html`, `rst`, ...). Formats without an entry use
+    /// `system_prompt`.
+    #[serde(default)]
+    pub system_prompts: BTreeMap<String, String>,
+    /// Replaces the built-in user prompt; `{content}` marks where the
+    /// document goes.
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    /// File holding the user prompt template, with a `{content}`
+    /// placeholder. Takes precedence over `prompt_template`.
+    #[serde(default)]
+    pub prompt_file: Option<PathBuf>,
+    /// USD per 1,000 input tokens, used for dry-run cost estimates.
+    #[serde(default = "default_price_per_1k_input")]
+    pub price_per_1k_input: f64,
+    /// USD per 1,000 output tokens, used for dry-run cost estimates.
+    #[serde(default = "default_price_per_1k_output")]
+    pub price_per_1k_output: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessingConfig {
     pub include_patterns: Vec<String>,
     pub exclude_patterns: Vec<String>,
+    /// Maximum directory recursion depth; `0` means unlimited.
     pub max_depth: usize,
+    /// Follow symlinks while walking input directories, including ones
+    /// pointing outside them. Symlinks are skipped when off.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Files larger than this are skipped without being read.
+    #[serde(default = "default_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+    /// Input token limit per request. Derived from the model's context window
+    /// and `max_tokens` when unset; longer inputs are truncated.
+    #[serde(default)]
+    pub max_input_tokens: Option<usize>,
+    /// Documents longer than this are split into chunks that are summarized
+    /// separately and then combined. Defaults to `max_input_tokens`.
+    #[serde(default)]
+    pub chunk_size_tokens: Option<usize>,
+    /// Tokens of trailing context repeated at the start of the next chunk.
+    #[serde(default = "default_chunk_overlap_tokens")]
+    pub chunk_overlap_tokens: usize,
+    /// Include text outputs of notebook code cells alongside their source.
+    #[serde(default = "default_notebook_outputs")]
+    pub notebook_outputs: bool,
+    /// `full` summarizes the whole text; `headings` reduces Markdown and
+    /// HTML to their headings and the first sentence of each section.
+    #[serde(default = "default_extract_mode")]
+    pub extract_mode: String,
+    /// Skip files that can only be decoded by replacing invalid bytes,
+    /// instead of summarizing the garbled text.
+    #[serde(default)]
+    pub strict_encoding: bool,
+    /// Glob pattern to format name (`markdown`, `txt`, `html`, ...) for
+    /// files whose extension gives the wrong format. The first matching
+    /// pattern in sorted order wins.
+    #[serde(default)]
+    pub format_overrides: BTreeMap<String, String>,
+    /// Documents of at most this many tokens are summarized several to a
+    /// request. `0` turns batching off.
+    #[serde(default)]
+    pub batch_threshold_tokens: usize,
+    /// Most documents packed into one batched request.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Number of files read and converted in parallel per input directory.
+    #[serde(default = "default_read_concurrency")]
+    pub read_concurrency: usize,
+    /// Number of documents summarized in parallel. Also read from the
+    /// `concurrency` key of older configs.
+    #[serde(default = "default_llm_concurrency", alias = "concurrency")]
+    pub llm_concurrency: usize,
+    /// Cleanup applied to every document after format-specific extraction.
+    #[serde(default)]
+    pub normalize: NormalizeConfig,
+}
+
+/// `[processing.normalize]`. Everything is off by default, so summaries
+/// and cache keys only change when opted in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NormalizeConfig {
+    /// Replace runs of blank lines with a single blank line.
+    #[serde(default)]
+    pub collapse_blank_lines: bool,
+    /// Strip spaces and tabs at the end of each line.
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+    /// Remove `http`, `https` and `ftp` URLs from the text.
+    #[serde(default)]
+    pub strip_urls: bool,
+    /// Written in place of each URL removed by `strip_urls`, e.g. `[link]`.
+    /// URLs are dropped without a trace when unset.
+    #[serde(default)]
+    pub url_placeholder: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OutputConfig {
     pub default_format: String,
     pub include_metadata: bool,
+    /// Produce one combined summary per input directory instead of one per file.
+    #[serde(default)]
+    pub merge: bool,
+    /// Number summaries in the order they are written, after sorting.
+    #[serde(default)]
+    pub numbered: bool,
+    /// Group Markdown summaries under a `## dir/` heading per top-level
+    /// directory of their input.
+    #[serde(default)]
+    pub group_by_directory: bool,
+    /// Heading of each summary in Markdown and plain text output, with
+    /// `{path}` for the original path and `{number}` for its position.
+    /// Defaults to `# Summary of {path}` and `=== {path} ===`
+    /// respectively, or `# {number}. Summary of {path}` and
+    /// `=== {number}. {path} ===` when `numbered` is set.
+    #[serde(default)]
+    pub heading_template: Option<String>,
+    /// Written after each summary in Markdown and plain text output.
+    /// Defaults to a `---` rule and a blank line respectively.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+fn default_temperature() -> f32 {
+    0.1
+}
+
+fn default_adaptive_min_tokens() -> usize {
+    128
+}
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_base_ms() -> u64 {
+    500
+}
+
+fn default_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_api_version() -> String {
+    "2024-02-01".to_string()
+}
+
+fn default_max_file_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_chunk_overlap_tokens() -> usize {
+    100
+}
+
+fn default_price_per_1k_input() -> f64 {
+    0.03
+}
+
+fn default_price_per_1k_output() -> f64 {
+    0.06
+}
+
+fn default_notebook_outputs() -> bool {
+    true
+}
+
+fn default_extract_mode() -> String {
+    "full".to_string()
+}
+
+fn default_read_concurrency() -> usize {
+    8
+}
+
+fn default_llm_concurrency() -> usize {
+    4
+}
+
+fn default_batch_size() -> usize {
+    8
 }
 
 impl Config {
-    pub fn load() -> Result<Self, ConfigError> {
-        let config_path = dirs::config_dir()
+    /// `config.toml` under the platform config directory.
+    pub fn default_path() -> Result<PathBuf, ConfigError> {
+        Ok(dirs::config_dir()
             .ok_or_else(|| ConfigError::IoError(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 "Could not find config directory",
             )))?
             .join("doctldr")
-            .join("config.toml");
+            .join("config.toml"))
+    }
+
+    pub fn load() -> Result<Self, ConfigError> {
+        let config_path = Self::default_path()?;
 
         if !config_path.exists() {
             return Ok(Self::default());
         }
 
-        let contents = std::fs::read_to_string(config_path)?;
-        Ok(toml::from_str(&contents)?)
+        Self::load_from(&config_path)
+    }
+
+    /// Loads an explicitly chosen config file. Unlike [`Config::load`], a
+    /// missing file is an error.
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut value: toml::Value = toml::from_str(&contents)?;
+        expand_env_vars(&mut value)?;
+        Ok(value.try_into()?)
+    }
+
+    /// The output format to write: `default.format` (which `--format` sets)
+    /// if given, otherwise `output.default_format`.
+    pub fn output_format(&self) -> &str {
+        if self.default.format.is_empty() {
+            &self.output.default_format
+        } else {
+            &self.default.format
+        }
+    }
+
+    /// Settings for reproducible runs (`--deterministic`): greedy sampling,
+    /// and one file read and one summary requested at a time, so summaries
+    /// are produced, streamed and batched in the same order every run.
+    /// The model itself may still answer differently.
+    pub fn make_deterministic(&mut self) {
+        self.default.temperature = 0.0;
+        self.processing.read_concurrency = 1;
+        self.processing.llm_concurrency = 1;
     }
+
+    /// Checks values whose valid range serde can't express.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(0.0..=2.0).contains(&self.default.temperature) {
+            return Err(ConfigError::InvalidValue(format!(
+                "temperature must be between 0.0 and 2.0, got {}",
+                self.default.temperature
+            )));
+        }
+        if self.default.min_tokens > self.default.max_tokens {
+            return Err(ConfigError::InvalidValue(format!(
+                "min_tokens ({}) must not exceed max_tokens ({})",
+                self.default.min_tokens, self.default.max_tokens
+            )));
+        }
+        if self.default.adaptive_tokens && self.default.adaptive_min_tokens > self.default.max_tokens {
+            return Err(ConfigError::InvalidValue(format!(
+                "adaptive_min_tokens ({}) must not exceed max_tokens ({})",
+                self.default.adaptive_min_tokens, self.default.max_tokens
+            )));
+        }
+        if let Some(name) = self.api.system_prompts.keys().find(|name| DocumentFormat::from_name(&name.to_lowercase()).is_none()) {
+            return Err(ConfigError::InvalidValue(format!("unknown format \"{}\" in system_prompts", name)));
+        }
+        if !["full", "headings"].contains(&self.processing.extract_mode.to_lowercase().as_str()) {
+            return Err(ConfigError::InvalidValue(format!(
+                "extract_mode must be \"full\" or \"headings\", got \"{}\"",
+                self.processing.extract_mode
+            )));
+        }
+        if self.processing.read_concurrency == 0 || self.processing.llm_concurrency == 0 {
+            return Err(ConfigError::InvalidValue(
+                "read_concurrency and llm_concurrency must be at least 1".to_string(),
+            ));
+        }
+        for (pattern, name) in &self.processing.format_overrides {
+            if DocumentFormat::from_name(&name.to_lowercase()).is_none() {
+                return Err(ConfigError::InvalidValue(format!(
+                    "unknown format \"{}\" for format_overrides pattern \"{}\"",
+                    name, pattern
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Expands `${VAR}` and `$VAR` references in every string of the parsed
+/// config. `$$` produces a literal `$`.
+fn expand_env_vars(value: &mut toml::Value) -> Result<(), ConfigError> {
+    match value {
+        toml::Value::String(text) => *text = expand_env_str(text)?,
+        toml::Value::Array(items) => {
+            for item in items {
+                expand_env_vars(item)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                expand_env_vars(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand_env_str(input: &str) -> Result<String, ConfigError> {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+
+        let name: String = match chars.peek() {
+            Some('$') => {
+                chars.next();
+                output.push('$');
+                continue;
+            }
+            Some('{') => {
+                chars.next();
+                chars.by_ref().take_while(|&c| c != '}').collect()
+            }
+            Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c != '_' && !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                name
+            }
+            _ => {
+                output.push('$');
+                continue;
+            }
+        };
+
+        let value = std::env::var(&name).map_err(|_| ConfigError::EnvVarNotFound(name))?;
+        output.push_str(&value);
+    }
+
+    Ok(output)
 }
 
 impl Default for Config {
@@ -71,12 +470,37 @@ impl Default for Config {
             default: DefaultConfig {
                 model: "gpt-4".to_string(),
                 max_tokens: 2048,
-                format: "md".to_string(),
+                format: String::new(),
                 verbose: false,
+                temperature: default_temperature(),
+                min_tokens: 0,
+                max_summary_chars: None,
+                self_check: false,
+                adaptive_tokens: false,
+                adaptive_min_tokens: default_adaptive_min_tokens(),
             },
             api: ApiConfig {
                 provider: "openai".to_string(),
                 key_env: "OPENAI_API_KEY".to_string(),
+                timeout_secs: default_timeout_secs(),
+                connect_timeout_secs: default_connect_timeout_secs(),
+                max_retries: default_max_retries(),
+                retry_base_ms: default_retry_base_ms(),
+                requests_per_minute: None,
+                proxy: None,
+                extra_headers: BTreeMap::new(),
+                base_url: default_base_url(),
+                ollama_url: None,
+                azure_endpoint: None,
+                azure_deployment: None,
+                api_version: default_api_version(),
+                context_window: None,
+                system_prompt: None,
+                system_prompts: BTreeMap::new(),
+                prompt_template: None,
+                prompt_file: None,
+                price_per_1k_input: default_price_per_1k_input(),
+                price_per_1k_output: default_price_per_1k_output(),
             },
             processing: ProcessingConfig {
                 include_patterns: vec![
@@ -90,11 +514,32 @@ impl Default for Config {
                     ".git".to_string(),
                 ],
                 max_depth: 5,
+                follow_symlinks: false,
+                max_file_size_bytes: default_max_file_size_bytes(),
+                max_input_tokens: None,
+                chunk_size_tokens: None,
+                chunk_overlap_tokens: default_chunk_overlap_tokens(),
+                notebook_outputs: default_notebook_outputs(),
+                extract_mode: default_extract_mode(),
+                strict_encoding: false,
+                format_overrides: BTreeMap::new(),
+                batch_threshold_tokens: 0,
+                batch_size: default_batch_size(),
+                read_concurrency: default_read_concurrency(),
+                llm_concurrency: default_llm_concurrency(),
+                normalize: NormalizeConfig::default(),
             },
             output: OutputConfig {
                 default_format: "md".to_string(),
                 include_metadata: true,
+                merge: false,
+                numbered: false,
+                group_by_directory: false,
+                heading_template: None,
+                separator: None,
             },
         }
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests;
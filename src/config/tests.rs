@@ -0,0 +1,50 @@
+use super::*;
+
+const FIXTURE: &str = r#"
+[default]
+model = "gpt-4o-mini"
+max_tokens = 512
+format = "json"
+verbose = false
+temperature = 0.2
+
+[api]
+provider = "openai"
+key_env = "DOCS_OPENAI_KEY"
+
+[processing]
+include_patterns = ["*.md", "*.adoc"]
+exclude_patterns = ["target"]
+max_depth = 0
+
+[output]
+default_format = "json"
+include_metadata = false
+"#;
+
+#[test]
+fn loads_an_explicit_toml_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doctldr.toml");
+    std::fs::write(&path, FIXTURE).unwrap();
+
+    let config = Config::load_from(&path).unwrap();
+
+    assert_eq!(config.default.model, "gpt-4o-mini");
+    assert_eq!(config.default.max_tokens, 512);
+    assert_eq!(config.default.temperature, 0.2);
+    assert_eq!(config.api.key_env, "DOCS_OPENAI_KEY");
+    assert_eq!(config.processing.include_patterns, ["*.md", "*.adoc"]);
+    assert_eq!(config.processing.max_depth, 0);
+    assert_eq!(config.output.default_format, "json");
+    assert!(!config.output.include_metadata);
+    // Omitted settings keep their defaults
+    assert_eq!(config.api.max_retries, Config::default().api.max_retries);
+}
+
+#[test]
+fn missing_explicit_file_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert!(matches!(Config::load_from(&dir.path().join("missing.toml")), Err(ConfigError::IoError(_))));
+}
@@ -0,0 +1,261 @@
+//! Crawling documentation sites for `--crawl`.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use reqwest::{Client, Url};
+use scraper::{Html, Selector};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use crate::processing::{Document, DocumentFormat, DocumentProcessor};
+
+/// Limits for one crawl.
+#[derive(Debug, Clone, Copy)]
+pub struct CrawlOptions {
+    /// Pages to summarize at most, the start page included.
+    pub max_pages: usize,
+    /// Links to follow away from the start page; `0` fetches only the start
+    /// page.
+    pub max_depth: usize,
+    /// Only follow links to the start page's host.
+    pub same_host: bool,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: 50,
+            max_depth: 3,
+            same_host: false,
+        }
+    }
+}
+
+/// Links to these are never followed; they aren't documents.
+const ASSET_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "ico", "css", "js", "mjs", "map", "json", "xml", "rss",
+    "zip", "gz", "tgz", "tar", "woff", "woff2", "ttf", "eot", "mp3", "mp4", "webm", "wasm",
+];
+
+/// Fetches `start` and the pages it links to, breadth first, and converts
+/// each one to a [`Document`]. Links are only extracted from HTML pages.
+/// Every host's `robots.txt` is honoured for `user_agent`. Pages that fail
+/// to load are logged and skipped, except the start page.
+pub async fn crawl(
+    processor: &DocumentProcessor,
+    client: &Client,
+    start: &str,
+    user_agent: &str,
+    options: &CrawlOptions,
+) -> Result<Vec<Document>> {
+    let start = Url::parse(start).with_context(|| format!("Invalid crawl URL: {}", start))?;
+    let mut robots: HashMap<String, Robots> = HashMap::new();
+    let mut seen = HashSet::from([start.as_str().to_string()]);
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    let mut documents = Vec::new();
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if documents.len() >= options.max_pages {
+            break;
+        }
+        let is_start = url == start;
+
+        let origin = url.origin().ascii_serialization();
+        if !robots.contains_key(&origin) {
+            let rules = Robots::fetch(client, &url, user_agent).await;
+            robots.insert(origin.clone(), rules);
+        }
+        if !robots[&origin].allows(&url) {
+            if is_start {
+                anyhow::bail!("robots.txt disallows crawling {}", url);
+            }
+            debug!("robots.txt disallows {}", url);
+            continue;
+        }
+
+        let page = match processor.fetch_url(url.as_str(), client).await {
+            Ok(page) => page,
+            Err(e) if is_start => return Err(e),
+            Err(e) => {
+                warn!("Skipping {}: {}", url, e);
+                continue;
+            }
+        };
+        // A redirect can lead off the host, or to a page already seen
+        if options.same_host && page.url.host_str() != start.host_str() {
+            debug!("Skipping {}: redirected off {}", url, start.host_str().unwrap_or_default());
+            continue;
+        }
+        if page.url != url && !seen.insert(page.url.as_str().to_string()) {
+            continue;
+        }
+
+        if depth < options.max_depth && page.format == DocumentFormat::Html {
+            for link in extract_links(&page.url, &page.bytes) {
+                if options.same_host && link.host_str() != start.host_str() {
+                    continue;
+                }
+                if is_asset(&link) {
+                    continue;
+                }
+                if seen.insert(link.as_str().to_string()) {
+                    queue.push_back((link, depth + 1));
+                }
+            }
+        }
+
+        match processor.process_bytes(Path::new(page.url.as_str()), page.bytes, page.format).await {
+            Ok(document) => documents.push(document),
+            Err(e) => warn!("Failed to process {}: {}", page.url, e),
+        }
+    }
+
+    info!("Crawled {} pages from {}", documents.len(), start);
+    Ok(documents)
+}
+
+/// Absolute http(s) links of an HTML page, without fragments. Links marked
+/// `rel="nofollow"` are left out.
+fn extract_links(base: &Url, html: &[u8]) -> Vec<Url> {
+    let document = Html::parse_document(&String::from_utf8_lossy(html));
+    let selector = Selector::parse("a[href]").expect("static selector");
+
+    document
+        .select(&selector)
+        .filter(|link| !link.value().attr("rel").is_some_and(|rel| rel.contains("nofollow")))
+        .filter_map(|link| base.join(link.value().attr("href")?).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .collect()
+}
+
+fn is_asset(url: &Url) -> bool {
+    Path::new(url.path())
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ASSET_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The `Allow` and `Disallow` rules of one host's robots.txt that apply to
+/// our user agent.
+#[derive(Debug, Default)]
+struct Robots {
+    rules: Vec<RobotsRule>,
+}
+
+#[derive(Debug)]
+struct RobotsRule {
+    allow: bool,
+    /// Length of the original pattern; the longest matching rule wins.
+    len: usize,
+    pattern: Regex,
+}
+
+impl Robots {
+    /// A missing or unreadable robots.txt allows everything.
+    async fn fetch(client: &Client, url: &Url, user_agent: &str) -> Self {
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return Self::default();
+        };
+
+        let response = match client.get(robots_url.clone()).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                debug!("No robots.txt at {} (HTTP {})", robots_url, response.status());
+                return Self::default();
+            }
+            Err(e) => {
+                debug!("Failed to fetch {}: {}", robots_url, e);
+                return Self::default();
+            }
+        };
+
+        match response.text().await {
+            Ok(text) => Self::parse(&text, user_agent),
+            Err(e) => {
+                debug!("Failed to read {}: {}", robots_url, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Uses the group naming our product token (`doctldr` in
+    /// `doctldr/0.1.0`), falling back to the `*` group.
+    fn parse(text: &str, user_agent: &str) -> Self {
+        let token = user_agent.split('/').next().unwrap_or(user_agent).trim().to_lowercase();
+        let mut groups: Vec<(Vec<String>, Vec<RobotsRule>)> = Vec::new();
+        let mut in_agents = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    // Consecutive user-agent lines share one group
+                    if !in_agents {
+                        groups.push((Vec::new(), Vec::new()));
+                    }
+                    in_agents = true;
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty Disallow allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let (Some((_, rules)), Some(pattern)) = (groups.last_mut(), robots_pattern(value)) {
+                        rules.push(RobotsRule {
+                            allow: key == "allow",
+                            len: value.len(),
+                            pattern,
+                        });
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+
+        let ours = groups.iter().position(|(agents, _)| agents.contains(&token));
+        let any = groups.iter().position(|(agents, _)| agents.iter().any(|agent| agent == "*"));
+        match ours.or(any) {
+            Some(index) => Self { rules: groups.swap_remove(index).1 },
+            None => Self::default(),
+        }
+    }
+
+    /// The longest matching rule decides; `Allow` wins ties.
+    fn allows(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.pattern.is_match(&path))
+            .max_by_key(|rule| (rule.len, rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// Compiles a robots.txt path pattern: a prefix in which `*` matches any
+/// characters and a trailing `$` anchors the end.
+fn robots_pattern(value: &str) -> Option<Regex> {
+    let (value, anchored) = match value.strip_suffix('$') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let pattern = regex::escape(value).replace(r"\*", ".*");
+    Regex::new(&format!("^{}{}", pattern, if anchored { "$" } else { "" })).ok()
+}
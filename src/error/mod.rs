@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::config::ConfigError;
+use crate::llm::{ApiError, IncompleteStream, NotCached};
+
+/// Errors of [`run`](crate::run) and
+/// [`LlmSummarizer::summarize`](crate::LlmSummarizer::summarize), by kind.
+/// The underlying error, with its context, is kept as the source.
+#[derive(Debug, Error)]
+pub enum DocTldrError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    /// Reading inputs, the cache or an earlier output failed.
+    #[error(transparent)]
+    Io(anyhow::Error),
+    /// An input couldn't be turned into text, e.g. a PDF without a text
+    /// layer or a lossily decoded file under `strict_encoding`.
+    #[error(transparent)]
+    Decode(anyhow::Error),
+    /// The provider failed or could not be reached, for reasons other than
+    /// those below.
+    #[error(transparent)]
+    Provider(anyhow::Error),
+    /// The provider kept answering 429 after all retries. Worth retrying
+    /// later, after `retry_after` if the provider said.
+    #[error("Rate limited by the API")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        #[source]
+        source: anyhow::Error,
+    },
+    /// The request didn't fit the model's context window; lowering
+    /// `processing.max_input_tokens` or `chunk_size_tokens` helps.
+    #[error(transparent)]
+    ContextExceeded(anyhow::Error),
+    /// Writing the summaries failed.
+    #[error(transparent)]
+    Output(anyhow::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// Classifies by the most specific error in the chain. Errors already
+/// classified where they occurred, like [`DocTldrError::Output`], keep
+/// their kind along with any context added since; configuration errors
+/// lose that context.
+impl From<anyhow::Error> for DocTldrError {
+    fn from(error: anyhow::Error) -> Self {
+        let classified = error.chain().find_map(|cause| cause.downcast_ref::<DocTldrError>());
+        let wrapped = error.chain().next().is_some_and(|cause| !cause.is::<DocTldrError>());
+        match classified {
+            Some(DocTldrError::Io(_)) if wrapped => return DocTldrError::Io(error),
+            Some(DocTldrError::Decode(_)) if wrapped => return DocTldrError::Decode(error),
+            Some(DocTldrError::Provider(_)) if wrapped => return DocTldrError::Provider(error),
+            Some(&DocTldrError::RateLimited { retry_after, .. }) if wrapped => {
+                return DocTldrError::RateLimited { retry_after, source: error };
+            }
+            Some(DocTldrError::ContextExceeded(_)) if wrapped => return DocTldrError::ContextExceeded(error),
+            Some(DocTldrError::Output(_)) if wrapped => return DocTldrError::Output(error),
+            Some(DocTldrError::Other(_)) if wrapped => return DocTldrError::Other(error),
+            // Not wrapped in context, or a `Config`, which has no room for it
+            Some(_) => {
+                return error.downcast::<DocTldrError>().unwrap_or_else(DocTldrError::Other);
+            }
+            None => {}
+        }
+
+        let error = match error.downcast::<ConfigError>() {
+            Ok(config) => return DocTldrError::Config(config),
+            Err(error) => error,
+        };
+        if let Some(api) = error.chain().find_map(|cause| cause.downcast_ref::<ApiError>()) {
+            return if api.is_rate_limited() {
+                DocTldrError::RateLimited { retry_after: api.retry_after, source: error }
+            } else if api.is_context_exceeded() {
+                DocTldrError::ContextExceeded(error)
+            } else {
+                DocTldrError::Provider(error)
+            };
+        }
+        if error.chain().any(|cause| {
+            cause.is::<reqwest::Error>() || cause.is::<IncompleteStream>() || cause.is::<NotCached>()
+        }) {
+            return DocTldrError::Provider(error);
+        }
+        if error.chain().any(|cause| cause.is::<std::io::Error>()) {
+            return DocTldrError::Io(error);
+        }
+        DocTldrError::Other(error)
+    }
+}
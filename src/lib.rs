@@ -0,0 +1,20 @@
+//! Summarizes documentation with LLMs. The `doctldr` binary is a thin CLI
+//! over [`run`]; the building blocks are exported for embedding.
+
+pub mod cache;
+pub mod config;
+pub mod crawl;
+pub mod error;
+pub mod llm;
+pub mod output;
+pub mod processing;
+mod run;
+pub mod source;
+pub mod testing;
+
+pub use config::Config;
+pub use error::DocTldrError;
+pub use llm::{LlmProvider, LlmSummarizer, OpenAiProvider};
+pub use output::{OutputWriter, Summary};
+pub use processing::{Document, DocumentProcessor};
+pub use run::{run, RunOptions, RunOutcome};
@@ -1,37 +1,476 @@
 use anyhow::{Result, Context};
 use async_trait::async_trait;
-use reqwest::Client;
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, ClientBuilder, Proxy, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::env;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use regex::Regex;
+use tiktoken_rs::CoreBPE;
+use tracing::{debug, warn};
+
+use crate::cache::SummaryCache;
+use crate::config::{ApiConfig, Config};
+use crate::error::DocTldrError;
+use crate::processing::DocumentFormat;
+
+pub const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434";
+
+/// Tokens reserved for the system prompt and instruction template.
+const PROMPT_OVERHEAD_TOKENS: usize = 300;
+
+/// Start of a summary that apologizes or refuses instead of summarizing.
+const REFUSAL_PATTERN: &str = r"(?i)^\W*(i'?m sorry|i am sorry|sorry,|i apologi[sz]e|my apologies|as an ai\b|i (cannot|can't|can not|am unable to|'m unable to|am not able to)\b)";
+
+/// Inputs of at least this many tokens whose summary has fewer than
+/// [`MIN_PLAUSIBLE_SUMMARY_TOKENS`] fail the self-check.
+const SELF_CHECK_MIN_INPUT_TOKENS: usize = 200;
+const MIN_PLAUSIBLE_SUMMARY_TOKENS: usize = 10;
+
+/// With `adaptive_tokens`, inputs get an output budget of one token per
+/// this many input tokens, within the configured bounds.
+const ADAPTIVE_TOKENS_DIVISOR: usize = 4;
+
+/// Summary-of-summaries rounds before falling back to truncation.
+const MAX_REDUCE_ROUNDS: usize = 3;
+
+/// Tokens reserved per document of a batched request for its delimiter
+/// lines.
+const BATCH_DELIMITER_TOKENS: usize = 10;
+
+const SYSTEM_PROMPT: &str = "You are a technical documentation processor focused on creating summaries for LLM consumption. \
+Your goal is to extract and preserve implementation details, concrete examples, and technical specifications \
+while eliminating general descriptions and conceptual explanations. Prioritize code examples, API specifications, \
+and exact usage patterns. Format your responses to maximize information density for LLM parsing.";
+
+/// Tokens consumed by one or more requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+/// A provider's answer to one summarization request.
+#[derive(Debug, Clone)]
+pub struct SummaryResult {
+    pub text: String,
+    /// `None` when the API didn't report token counts.
+    pub usage: Option<TokenUsage>,
+    /// The model that answered, as reported by the API, which may be more
+    /// specific than the one requested (`gpt-4` -> `gpt-4-0613`).
+    pub model: String,
+}
+
+/// One chat message of a request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
 
 #[async_trait]
 pub trait LlmProvider {
-    async fn summarize(&self, content: &str, max_tokens: usize) -> Result<String>;
+    async fn summarize(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<SummaryResult>;
+
+    /// The system and user messages [`LlmProvider::summarize`] sends for
+    /// `content`, built without any network access. Defaults to the
+    /// built-in prompts.
+    fn build_messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        Prompts::default().messages(content, format)
+    }
+
+    /// Whether [`LlmProvider::summarize_batch`] packs documents into one
+    /// request. Documents are only batched for providers that do.
+    fn supports_batch(&self) -> bool {
+        false
+    }
+
+    /// Summarizes several documents, returning one result per document in
+    /// the same order. `max_tokens` limits each summary. Providers that can
+    /// pack documents into one request override this, along with
+    /// [`LlmProvider::supports_batch`]; the default sends one request per
+    /// document.
+    async fn summarize_batch(&self, contents: &[String], format: Option<DocumentFormat>, max_tokens: usize) -> Result<Vec<SummaryResult>> {
+        let mut results = Vec::with_capacity(contents.len());
+        for content in contents {
+            results.push(self.summarize(content, format, max_tokens).await?);
+        }
+        Ok(results)
+    }
+
+    /// Yields the summary incrementally as it is generated. Providers without
+    /// native streaming fall back to a single chunk holding the full summary.
+    async fn summarize_stream(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<BoxStream<'static, Result<String>>> {
+        let summary = self.summarize(content, format, max_tokens).await?;
+        Ok(stream::once(async move { Ok(summary.text) }).boxed())
+    }
+}
+
+/// Settings shared by every provider.
+#[derive(Debug, Clone)]
+pub struct ProviderOptions {
+    /// HTTP client with the configured request and connect timeouts.
+    pub client: Client,
+    pub retry: RetryPolicy,
+    /// Shared by every request, retries included, to stay under
+    /// `api.requests_per_minute`.
+    pub rate_limit: Option<Arc<DefaultDirectRateLimiter>>,
+    pub prompts: Prompts,
+    pub temperature: f32,
+}
+
+impl ProviderOptions {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.api.extra_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name in api.extra_headers: {}", name))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header {} in api.extra_headers", name))?;
+            headers.insert(name, value);
+        }
+        let client = with_proxy(Client::builder(), &config.api)?
+            .default_headers(headers)
+            .timeout(Duration::from_secs(config.api.timeout_secs))
+            .connect_timeout(Duration::from_secs(config.api.connect_timeout_secs))
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            client,
+            retry: RetryPolicy::from_config(&config.api),
+            rate_limit: config.api.requests_per_minute
+                .and_then(NonZeroU32::new)
+                .map(|rpm| Arc::new(RateLimiter::direct(Quota::per_minute(rpm)))),
+            prompts: Prompts::from_config(&config.api)?,
+            temperature: config.default.temperature,
+        })
+    }
+}
+
+/// Routes `builder`'s requests through `api.proxy`. Without it, reqwest
+/// uses the proxy environment variables.
+pub fn with_proxy(builder: ClientBuilder, config: &ApiConfig) -> Result<ClientBuilder> {
+    match &config.proxy {
+        Some(url) => {
+            let proxy = Proxy::all(url).with_context(|| format!("Invalid api.proxy URL: {}", url))?;
+            Ok(builder.proxy(proxy))
+        }
+        None => Ok(builder),
+    }
+}
+
+/// The system prompt and user prompt template sent with each request.
+/// Either falls back to the built-in default when not configured.
+#[derive(Debug, Clone, Default)]
+pub struct Prompts {
+    system: Option<String>,
+    by_format: HashMap<DocumentFormat, String>,
+    template: Option<String>,
+}
+
+impl Prompts {
+    /// Reads `prompt_file` when set; it must exist and contain `{content}`.
+    pub fn from_config(config: &ApiConfig) -> Result<Self> {
+        let template = match &config.prompt_file {
+            Some(path) => {
+                let template = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read prompt file {}", path.display()))?;
+                if !template.contains("{content}") {
+                    anyhow::bail!(
+                        "Prompt file {} has no {{content}} placeholder for the document",
+                        path.display()
+                    );
+                }
+                Some(template)
+            }
+            None => config.prompt_template.clone(),
+        };
+
+        let by_format = config.system_prompts
+            .iter()
+            .map(|(name, prompt)| {
+                let format = DocumentFormat::from_name(&name.to_lowercase())
+                    .with_context(|| format!("Unknown format in api.system_prompts: {}", name))?;
+                Ok((format, prompt.clone()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            system: config.system_prompt.clone(),
+            by_format,
+            template,
+        })
+    }
+
+    /// The system prompt for documents of `format`: its entry in
+    /// `api.system_prompts`, else `api.system_prompt`, else the built-in one.
+    pub fn system(&self, format: Option<DocumentFormat>) -> &str {
+        format
+            .and_then(|format| self.by_format.get(&format))
+            .or(self.system.as_ref())
+            .map(String::as_str)
+            .unwrap_or(SYSTEM_PROMPT)
+    }
+
+    /// The system message followed by the user message for `content`.
+    pub fn messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        vec![
+            Message {
+                role: "system".to_string(),
+                content: self.system(format).to_string(),
+            },
+            Message {
+                role: "user".to_string(),
+                content: self.user(content),
+            },
+        ]
+    }
+
+    /// Fills `{content}` in the template. A template without the placeholder
+    /// gets the content appended after a blank line.
+    pub fn user(&self, content: &str) -> String {
+        match &self.template {
+            Some(template) if template.contains("{content}") => template.replace("{content}", content),
+            Some(template) => format!("{}\n\n{}", template, content),
+            None => create_summary_prompt(content),
+        }
+    }
+}
+
+/// How often and how patiently to retry transient API failures.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            base_delay: Duration::from_millis(config.retry_base_ms),
+        }
+    }
+
+    /// Exponential backoff with up to 50% random jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(1 << attempt.min(16));
+        let jitter_ms = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+        delay + Duration::from_millis(jitter_ms)
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503)
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Sends the request built by `build`, retrying transient failures per `policy`.
+/// Non-retryable error statuses (e.g. 400/401) are returned immediately.
+async fn send_with_retry<F>(options: &ProviderOptions, build: F) -> Result<Response>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let policy = &options.retry;
+    let mut attempt = 0;
+    loop {
+        if let Some(limiter) = &options.rate_limit {
+            limiter.until_ready().await;
+        }
+
+        let delay = match build().send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                if !is_retryable_status(status) || attempt >= policy.max_retries {
+                    return Err(api_error(response).await);
+                }
+                let delay = retry_after(&response).unwrap_or_else(|| policy.backoff(attempt));
+                warn!("API returned {}, retrying in {:?}", status, delay);
+                delay
+            }
+            Err(e) if (e.is_timeout() || e.is_connect()) && attempt < policy.max_retries => {
+                let delay = policy.backoff(attempt);
+                warn!("API request failed ({}), retrying in {:?}", e, delay);
+                delay
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// A non-success response from a provider, after any retries.
+#[derive(Debug, thiserror::Error)]
+#[error("API request failed with status {status}{}", if message.is_empty() { String::new() } else { format!(": {}", message) })]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// The provider's `error.message` when the body is JSON, else the body.
+    pub message: String,
+    /// The provider's `error.code` or `error.type`, e.g. `context_length_exceeded`.
+    pub code: Option<String>,
+    /// The `Retry-After` of a 429 response.
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Whether the request was rejected for not fitting the model's context
+    /// window. Providers word this differently, so both the error code and
+    /// the message are checked.
+    pub fn is_context_exceeded(&self) -> bool {
+        let message = self.message.to_lowercase();
+        self.code.as_deref() == Some("context_length_exceeded")
+            || ["maximum context length", "context window", "prompt is too long", "too many tokens"]
+                .iter()
+                .any(|phrase| message.contains(phrase))
+    }
+}
+
+/// Turns a non-success response into an [`ApiError`].
+async fn api_error(response: Response) -> anyhow::Error {
+    let status = response.status();
+    let retry_after = retry_after(&response);
+    let body = response.text().await.unwrap_or_default();
+    let error = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|value| value.get("error").cloned());
+    let message = error.as_ref().and_then(|error| {
+        error
+            .get("message")
+            .and_then(|message| message.as_str())
+            .or_else(|| error.as_str())
+            .map(str::to_string)
+    });
+    let code = error.as_ref().and_then(|error| {
+        error
+            .get("code")
+            .or_else(|| error.get("type"))
+            .and_then(|code| code.as_str())
+            .map(str::to_string)
+    });
+
+    anyhow::Error::new(ApiError {
+        status,
+        message: message.unwrap_or_else(|| body.trim().to_string()),
+        code,
+        retry_after,
+    })
+}
+
+fn create_summary_prompt(content: &str) -> String {
+    format!(
+        "Create a technical summary optimized for an LLM to understand how to use and implement this tool/feature. Focus on:
+1. Function signatures, types, and interfaces
+2. Concrete usage examples with actual parameters
+3. Key implementation details and data structures
+4. API endpoints and their request/response formats
+5. Configuration options with specific valid values
+6. Command-line usage patterns with real examples
+
+Exclude:
+- General descriptions without technical details
+- Marketing or promotional content
+- Basic setup instructions unless they contain specific commands
+- Conceptual explanations without code or concrete examples
+
+Format the response to maximize information density while maintaining clear structure.
+If the documentation contains code examples, preserve them with their context.
+
+Documentation to summarize:
+
+{}",
+        content
+    )
 }
 
 pub struct OpenAiProvider {
     client: Client,
     api_key: String,
+    base_url: String,
     model: String,
+    options: ProviderOptions,
 }
 
 #[derive(Debug, Serialize)]
 struct OpenAiRequest {
     model: String,
-    messages: Vec<OpenAiMessage>,
+    messages: Vec<Message>,
     max_tokens: usize,
     temperature: f32,
-}
-
-#[derive(Debug, Serialize)]
-struct OpenAiMessage {
-    role: String,
-    content: String,
+    stream: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAiResponse {
     choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiUsage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+}
+
+impl OpenAiResponse {
+    /// Shared by OpenAI and Azure OpenAI; `model` stands in when the
+    /// response doesn't name one.
+    fn into_result(self, model: &str, api: &str) -> Result<SummaryResult> {
+        let text = self.choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .with_context(|| format!("No response from {} API", api))?;
+
+        Ok(SummaryResult {
+            text,
+            usage: self.usage.map(|usage| TokenUsage {
+                input_tokens: usage.prompt_tokens,
+                output_tokens: usage.completion_tokens,
+            }),
+            model: self.model.unwrap_or_else(|| model.to_string()),
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,97 +483,1347 @@ struct OpenAiResponseMessage {
     content: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct OpenAiStreamChunk {
+    choices: Vec<OpenAiStreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiStreamChoice {
+    delta: OpenAiDelta,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
 impl OpenAiProvider {
-    pub fn new(model: String) -> Result<Self> {
-        let api_key = env::var("OPENAI_API_KEY")
-            .context("OPENAI_API_KEY environment variable not found")?;
+    /// `base_url` lets any OpenAI-compatible API stand in for OpenAI's own.
+    pub fn new(api_key: String, base_url: String, model: String, options: ProviderOptions) -> Self {
+        Self {
+            client: options.client.clone(),
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            options,
+        }
+    }
 
-        Ok(Self {
-            client: Client::new(),
+    async fn send(&self, request: &OpenAiRequest) -> Result<Response> {
+        send_with_retry(&self.options, || {
+            self.client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(request)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn build_messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        self.options.prompts.messages(content, format)
+    }
+
+    async fn summarize(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<SummaryResult> {
+        let request = openai_request(&self.model, &self.options, content, format, max_tokens, false);
+        self.send(&request).await?
+            .json::<OpenAiResponse>()
+            .await?
+            .into_result(&self.model, "OpenAI")
+    }
+
+    fn supports_batch(&self) -> bool {
+        true
+    }
+
+    async fn summarize_batch(&self, contents: &[String], format: Option<DocumentFormat>, max_tokens: usize) -> Result<Vec<SummaryResult>> {
+        let request = openai_request(&self.model, &self.options, &batch_prompt(contents), format, max_tokens * contents.len(), false);
+        let result = self.send(&request).await?
+            .json::<OpenAiResponse>()
+            .await?
+            .into_result(&self.model, "OpenAI")?;
+        split_batch_result(result, contents)
+    }
+
+    async fn summarize_stream(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<BoxStream<'static, Result<String>>> {
+        let request = openai_request(&self.model, &self.options, content, format, max_tokens, true);
+        let response = self.send(&request).await?;
+        Ok(openai_sse_stream(response))
+    }
+}
+
+/// Builds a chat completions request; shared by OpenAI and Azure OpenAI.
+fn openai_request(
+    model: &str,
+    options: &ProviderOptions,
+    content: &str,
+    format: Option<DocumentFormat>,
+    max_tokens: usize,
+    stream: bool,
+) -> OpenAiRequest {
+    OpenAiRequest {
+        model: model.to_string(),
+        messages: options.prompts.messages(content, format),
+        max_tokens,
+        temperature: options.temperature,
+        stream,
+    }
+}
+
+/// The content of a request summarizing all of `contents` at once: each
+/// document follows a numbered `=== DOCUMENT n ===` line, and the model is
+/// asked to start each summary with the matching `=== SUMMARY n ===` line.
+/// The result still goes through the configured prompt template.
+fn batch_prompt(contents: &[String]) -> String {
+    let mut prompt = format!(
+        "The following {} documents are each introduced by a \"=== DOCUMENT n ===\" line. \
+Summarize every document separately. Start each summary with a line \"=== SUMMARY n ===\", \
+where n is the number of the document it summarizes, and write nothing outside these sections.",
+        contents.len()
+    );
+    for (index, content) in contents.iter().enumerate() {
+        prompt.push_str(&format!("\n\n=== DOCUMENT {} ===\n{}", index + 1, content));
+    }
+    prompt
+}
+
+/// Splits the answer to a [`batch_prompt`] into one result per document.
+/// The reported usage is divided among them, input tokens by document
+/// length and output tokens by summary length. Fails unless every document
+/// got exactly one non-empty summary.
+fn split_batch_result(result: SummaryResult, contents: &[String]) -> Result<Vec<SummaryResult>> {
+    let mut sections: Vec<Option<String>> = vec![None; contents.len()];
+    let mut current: Option<usize> = None;
+
+    for line in result.text.lines() {
+        let number = line.trim()
+            .strip_prefix("=== SUMMARY ")
+            .and_then(|rest| rest.strip_suffix(" ==="))
+            .and_then(|number| number.trim().parse::<usize>().ok());
+        match number {
+            Some(number) if (1..=contents.len()).contains(&number) => {
+                if sections[number - 1].is_some() {
+                    anyhow::bail!("Batched response has two summaries for document {}", number);
+                }
+                sections[number - 1] = Some(String::new());
+                current = Some(number - 1);
+            }
+            Some(number) => anyhow::bail!("Batched response has a summary for unknown document {}", number),
+            None => {
+                if let Some(section) = current.and_then(|index| sections[index].as_mut()) {
+                    section.push_str(line);
+                    section.push('\n');
+                }
+            }
+        }
+    }
+
+    let texts = sections
+        .into_iter()
+        .enumerate()
+        .map(|(index, section)| match section.map(|text| text.trim().to_string()) {
+            Some(text) if !text.is_empty() => Ok(text),
+            _ => Err(anyhow::anyhow!("Batched response has no summary for document {}", index + 1)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let input_total: usize = contents.iter().map(String::len).sum();
+    let output_total: usize = texts.iter().map(String::len).sum();
+    Ok(contents
+        .iter()
+        .zip(texts)
+        .map(|(content, text)| SummaryResult {
+            usage: result.usage.map(|usage| TokenUsage {
+                input_tokens: usage.input_tokens * content.len() / input_total.max(1),
+                output_tokens: usage.output_tokens * text.len() / output_total.max(1),
+            }),
+            model: result.model.clone(),
+            text,
+        })
+        .collect())
+}
+
+/// Azure OpenAI speaks the chat completions protocol, but routes by
+/// deployment name and authenticates with an `api-key` header.
+pub struct AzureOpenAiProvider {
+    client: Client,
+    api_key: String,
+    url: String,
+    deployment: String,
+    options: ProviderOptions,
+}
+
+impl AzureOpenAiProvider {
+    pub fn new(
+        api_key: String,
+        endpoint: &str,
+        deployment: String,
+        api_version: &str,
+        options: ProviderOptions,
+    ) -> Self {
+        let url = format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint.trim_end_matches('/'),
+            deployment,
+            api_version
+        );
+
+        Self {
+            client: options.client.clone(),
             api_key,
+            url,
+            deployment,
+            options,
+        }
+    }
+
+    async fn send(&self, request: &OpenAiRequest) -> Result<Response> {
+        send_with_retry(&self.options, || {
+            self.client
+                .post(&self.url)
+                .header("api-key", &self.api_key)
+                .json(request)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AzureOpenAiProvider {
+    fn build_messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        self.options.prompts.messages(content, format)
+    }
+
+    async fn summarize(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<SummaryResult> {
+        let request = openai_request(&self.deployment, &self.options, content, format, max_tokens, false);
+        self.send(&request).await?
+            .json::<OpenAiResponse>()
+            .await?
+            .into_result(&self.deployment, "Azure OpenAI")
+    }
+
+    fn supports_batch(&self) -> bool {
+        true
+    }
+
+    async fn summarize_batch(&self, contents: &[String], format: Option<DocumentFormat>, max_tokens: usize) -> Result<Vec<SummaryResult>> {
+        let request = openai_request(&self.deployment, &self.options, &batch_prompt(contents), format, max_tokens * contents.len(), false);
+        let result = self.send(&request).await?
+            .json::<OpenAiResponse>()
+            .await?
+            .into_result(&self.deployment, "Azure OpenAI")?;
+        split_batch_result(result, contents)
+    }
+
+    async fn summarize_stream(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<BoxStream<'static, Result<String>>> {
+        let request = openai_request(&self.deployment, &self.options, content, format, max_tokens, true);
+        let response = self.send(&request).await?;
+        Ok(openai_sse_stream(response))
+    }
+}
+
+/// Parses OpenAI's server-sent events into the content deltas they carry.
+/// Lines are buffered as bytes so multi-byte characters split across
+/// network chunks decode correctly.
+/// A stream that ends without OpenAI's `[DONE]` sentinel fails with
+/// [`IncompleteStream`] after its last delta.
+fn openai_sse_stream(response: Response) -> BoxStream<'static, Result<String>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let done = Arc::new(AtomicBool::new(false));
+    let saw_done = Arc::clone(&done);
+
+    let deltas = response
+        .bytes_stream()
+        .map(move |chunk| -> Result<Vec<String>> {
+            buffer.extend_from_slice(&chunk?);
+            let mut deltas = Vec::new();
+
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    saw_done.store(true, Ordering::Relaxed);
+                    continue;
+                }
+
+                let chunk: OpenAiStreamChunk = serde_json::from_str(data)
+                    .context("Failed to parse OpenAI stream chunk")?;
+                deltas.extend(chunk.choices.into_iter().filter_map(|choice| choice.delta.content));
+            }
+
+            Ok(deltas)
+        })
+        .map_ok(|deltas| stream::iter(deltas.into_iter().map(Ok)))
+        .try_flatten();
+
+    let check = stream::once(async move {
+        (!done.load(Ordering::Relaxed)).then(|| Err(anyhow::Error::new(IncompleteStream)))
+    })
+    .filter_map(future::ready);
+
+    deltas.chain(check).boxed()
+}
+
+/// A streamed summary was cut off, usually by a dropped connection. What
+/// arrived is incomplete; requesting the summary again is worthwhile.
+#[derive(Debug, thiserror::Error)]
+#[error("The response stream ended before the summary was complete")]
+pub struct IncompleteStream;
+
+/// A summary wasn't in the cache, and the summarizer was built with
+/// [`LlmSummarizer::offline`], so the API wasn't asked for it.
+#[derive(Debug, thiserror::Error)]
+#[error("No cached summary, and offline mode forbids API calls")]
+pub struct NotCached;
+
+/// Stands in for a real provider in offline mode; every request fails
+/// with [`NotCached`].
+struct OfflineProvider;
+
+#[async_trait]
+impl LlmProvider for OfflineProvider {
+    async fn summarize(&self, _content: &str, _format: Option<DocumentFormat>, _max_tokens: usize) -> Result<SummaryResult> {
+        Err(anyhow::Error::new(NotCached))
+    }
+}
+
+/// Talks to a local Ollama server. No API key is needed.
+pub struct OllamaProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+    options: ProviderOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest {
+    model: String,
+    system: String,
+    prompt: String,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    num_predict: usize,
+    temperature: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    response: String,
+    #[serde(default)]
+    model: Option<String>,
+    /// Omitted when the prompt was served from Ollama's cache.
+    #[serde(default)]
+    prompt_eval_count: Option<usize>,
+    #[serde(default)]
+    eval_count: Option<usize>,
+}
+
+impl OllamaProvider {
+    pub fn new(model: String, base_url: String, options: ProviderOptions) -> Self {
+        Self {
+            client: options.client.clone(),
+            base_url: base_url.trim_end_matches('/').to_string(),
             model,
+            options,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn build_messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        self.options.prompts.messages(content, format)
+    }
+
+    async fn summarize(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<SummaryResult> {
+        let request = OllamaRequest {
+            model: self.model.clone(),
+            system: self.options.prompts.system(format).to_string(),
+            prompt: self.options.prompts.user(content),
+            stream: false,
+            options: OllamaOptions {
+                num_predict: max_tokens,
+                temperature: self.options.temperature,
+            },
+        };
+
+        let response = send_with_retry(&self.options, || {
+            self.client
+                .post(format!("{}/api/generate", self.base_url))
+                .json(&request)
+        })
+        .await
+        .with_context(|| format!("Failed to reach Ollama at {}", self.base_url))?
+        .json::<OllamaResponse>()
+        .await?;
+
+        Ok(SummaryResult {
+            text: response.response,
+            usage: match (response.prompt_eval_count, response.eval_count) {
+                (Some(input_tokens), Some(output_tokens)) => Some(TokenUsage { input_tokens, output_tokens }),
+                _ => None,
+            },
+            model: response.model.unwrap_or_else(|| self.model.clone()),
         })
     }
+}
 
-    fn create_summary_prompt(content: &str) -> String {
-        format!(
-            "Create a technical summary optimized for an LLM to understand how to use and implement this tool/feature. Focus on:
-1. Function signatures, types, and interfaces
-2. Concrete usage examples with actual parameters
-3. Key implementation details and data structures
-4. API endpoints and their request/response formats
-5. Configuration options with specific valid values
-6. Command-line usage patterns with real examples
+pub struct AnthropicProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+    options: ProviderOptions,
+}
 
-Exclude:
-- General descriptions without technical details
-- Marketing or promotional content
-- Basic setup instructions unless they contain specific commands
-- Conceptual explanations without code or concrete examples
+#[derive(Debug, Serialize)]
+struct AnthropicRequest {
+    model: String,
+    system: String,
+    messages: Vec<Message>,
+    max_tokens: usize,
+    temperature: f32,
+}
 
-Format the response to maximize information density while maintaining clear structure.
-If the documentation contains code examples, preserve them with their context.
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
 
-Documentation to summarize:
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    input_tokens: usize,
+    output_tokens: usize,
+}
 
-{}",
-            content
-        )
+#[derive(Debug, Deserialize)]
+struct AnthropicContent {
+    #[serde(default)]
+    text: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: String, model: String, options: ProviderOptions) -> Self {
+        Self {
+            client: options.client.clone(),
+            api_key,
+            model,
+            options,
+        }
     }
 }
 
 #[async_trait]
-impl LlmProvider for OpenAiProvider {
-    async fn summarize(&self, content: &str, max_tokens: usize) -> Result<String> {
-        let request = OpenAiRequest {
+impl LlmProvider for AnthropicProvider {
+    fn build_messages(&self, content: &str, format: Option<DocumentFormat>) -> Vec<Message> {
+        self.options.prompts.messages(content, format)
+    }
+
+    async fn summarize(&self, content: &str, format: Option<DocumentFormat>, max_tokens: usize) -> Result<SummaryResult> {
+        let request = AnthropicRequest {
             model: self.model.clone(),
-            messages: vec![
-                OpenAiMessage {
-                    role: "system".to_string(),
-                    content: "You are a technical documentation processor focused on creating summaries for LLM consumption. \
-                    Your goal is to extract and preserve implementation details, concrete examples, and technical specifications \
-                    while eliminating general descriptions and conceptual explanations. Prioritize code examples, API specifications, \
-                    and exact usage patterns. Format your responses to maximize information density for LLM parsing.".to_string(),
-                },
-                OpenAiMessage {
-                    role: "user".to_string(),
-                    content: Self::create_summary_prompt(content),
-                },
-            ],
+            system: self.options.prompts.system(format).to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: self.options.prompts.user(content),
+            }],
             max_tokens,
-            temperature: 0.1,
+            // Anthropic only accepts 0.0-1.0
+            temperature: self.options.temperature.min(1.0),
         };
 
-        let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&request)
-            .send()
-            .await?
-            .json::<OpenAiResponse>()
-            .await?;
+        let response = send_with_retry(&self.options, || {
+            self.client
+                .post("https://api.anthropic.com/v1/messages")
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .json(&request)
+        })
+        .await?
+        .json::<AnthropicResponse>()
+        .await?;
 
-        response.choices
-            .first()
-            .map(|choice| choice.message.content.clone())
-            .context("No response from OpenAI API")
+        let text: String = response.content.into_iter().map(|block| block.text).collect();
+        if text.is_empty() {
+            anyhow::bail!("No response from Anthropic API");
+        }
+        Ok(SummaryResult {
+            text,
+            usage: response.usage.map(|usage| TokenUsage {
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+            }),
+            model: response.model.unwrap_or_else(|| self.model.clone()),
+        })
     }
 }
 
+/// Provider names accepted by `api.provider`.
+pub const PROVIDERS: &[&str] = &["openai", "anthropic", "ollama", "azure"];
+
+/// Builds the provider named by `api.provider`, reading its key from `api.key_env`.
+pub fn provider_from_config(config: &Config) -> Result<Box<dyn LlmProvider + Send + Sync>> {
+    let options = ProviderOptions::from_config(config)?;
+    let model = config.default.model.clone();
+    let config = &config.api;
+    let provider: Box<dyn LlmProvider + Send + Sync> = match config.provider.to_lowercase().as_str() {
+        "openai" => Box::new(OpenAiProvider::new(
+            api_key_from_env(&config.key_env)?,
+            config.base_url.clone(),
+            model,
+            options,
+        )),
+        "anthropic" => Box::new(AnthropicProvider::new(api_key_from_env(&config.key_env)?, model, options)),
+        "ollama" => Box::new(OllamaProvider::new(
+            model,
+            config.ollama_url.clone().unwrap_or_else(|| OLLAMA_DEFAULT_URL.to_string()),
+            options,
+        )),
+        "azure" => {
+            let endpoint = config.azure_endpoint.as_deref()
+                .context("api.azure_endpoint must be set for the azure provider")?;
+            // Deployments are often named after the model they serve
+            let deployment = config.azure_deployment.clone().unwrap_or(model);
+            Box::new(AzureOpenAiProvider::new(
+                api_key_from_env(&config.key_env)?,
+                endpoint,
+                deployment,
+                &config.api_version,
+                options,
+            ))
+        }
+        other => anyhow::bail!(
+            "Unsupported LLM provider: {} (expected one of: {})",
+            other,
+            PROVIDERS.join(", ")
+        ),
+    };
+
+    Ok(provider)
+}
+
+fn api_key_from_env(key_env: &str) -> Result<String> {
+    env::var(key_env).with_context(|| format!("{} environment variable not found", key_env))
+}
+
+/// Context window sizes by model name prefix, checked in order so longer
+/// prefixes come first. Models not listed fall back to tiktoken's table.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo-instruct", 4_096),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("claude-2", 100_000),
+    ("claude-", 200_000),
+    ("llama3.1", 128_000),
+    ("llama3.2", 128_000),
+    ("llama3.3", 128_000),
+    ("llama3", 8_192),
+    ("llama2", 4_096),
+    ("mistral-nemo", 128_000),
+    ("mistral", 32_768),
+    ("mixtral", 32_768),
+    ("qwen2.5", 32_768),
+    ("gemma2", 8_192),
+];
+
+/// Context window of `model` in tokens.
+pub fn context_window(model: &str) -> usize {
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|&(_, size)| size)
+        .unwrap_or_else(|| tiktoken_rs::model::get_context_size(model))
+}
+
+/// Counts tokens with the model's tiktoken encoding, falling back to
+/// `cl100k_base` for models tiktoken doesn't know.
+pub struct TokenCounter {
+    bpe: CoreBPE,
+}
+
+impl TokenCounter {
+    pub fn for_model(model: &str) -> Result<Self> {
+        let bpe = tiktoken_rs::get_bpe_from_model(model)
+            .or_else(|_| tiktoken_rs::cl100k_base())
+            .context("Failed to load tokenizer")?;
+        Ok(Self { bpe })
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> Result<String> {
+        let mut tokens = self.bpe.encode_with_special_tokens(text);
+        tokens.truncate(max_tokens);
+        self.bpe.decode(tokens)
+    }
+
+    /// Splits text into chunks of roughly `max_tokens` on paragraph
+    /// boundaries. Up to `overlap` tokens of trailing paragraphs are repeated
+    /// at the start of the next chunk to keep context across the cut.
+    pub fn split_into_chunks(&self, text: &str, max_tokens: usize, overlap: usize) -> Result<Vec<String>> {
+        let mut chunks = Vec::new();
+        let mut current: Vec<(String, usize)> = Vec::new();
+        let mut current_tokens = 0;
+
+        for paragraph in text.split("\n\n").filter(|p| !p.trim().is_empty()) {
+            for piece in self.split_oversized(paragraph, max_tokens)? {
+                let count = self.count(&piece);
+
+                if current_tokens + count > max_tokens && !current.is_empty() {
+                    chunks.push(join_paragraphs(&current));
+
+                    let mut carried = Vec::new();
+                    let mut carried_tokens = 0;
+                    for (text, tokens) in current.iter().rev() {
+                        if carried_tokens + tokens > overlap || carried_tokens + tokens + count > max_tokens {
+                            break;
+                        }
+                        carried.push((text.clone(), *tokens));
+                        carried_tokens += tokens;
+                    }
+                    carried.reverse();
+                    current = carried;
+                    current_tokens = carried_tokens;
+                }
+
+                current_tokens += count;
+                current.push((piece, count));
+            }
+        }
+
+        if !current.is_empty() {
+            chunks.push(join_paragraphs(&current));
+        }
+        Ok(chunks)
+    }
+
+    /// Hard-splits a single paragraph that is larger than a whole chunk.
+    fn split_oversized(&self, paragraph: &str, max_tokens: usize) -> Result<Vec<String>> {
+        let tokens = self.bpe.encode_with_special_tokens(paragraph);
+        if tokens.len() <= max_tokens {
+            return Ok(vec![paragraph.to_string()]);
+        }
+
+        tokens
+            .chunks(max_tokens.max(1))
+            .map(|piece| self.bpe.decode(piece.to_vec()))
+            .collect()
+    }
+}
+
+fn join_paragraphs(paragraphs: &[(String, usize)]) -> String {
+    paragraphs
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// A generated summary along with how it was produced.
+#[derive(Debug)]
+pub struct GeneratedSummary {
+    pub text: String,
+    /// Number of chunks the document was split into (1 when it fit in one request).
+    pub chunks: usize,
+    /// The model that wrote the final summary.
+    pub model: String,
+    /// Tokens consumed across every request for the document, chunk
+    /// summaries and retries for length included. Zero for cache hits.
+    pub usage: TokenUsage,
+    /// Whether the summary was requested again for failing the self-check.
+    pub retried: bool,
+    /// Output token budget chosen for the final request; see
+    /// `default.adaptive_tokens`.
+    pub max_tokens: usize,
+}
+
+/// How a run's summary requests were served, from
+/// [`LlmSummarizer::cache_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    /// Requests answered from the summary cache.
+    pub hits: usize,
+    /// Requests sent to the API, with or without a cache.
+    pub misses: usize,
+    /// Estimated tokens the cache hits would have cost.
+    pub saved: TokenUsage,
+}
+
+/// A streamed summary. `chunks` is known up front since any chunk summaries
+/// are generated before the final pass starts streaming. `usage` covers the
+/// chunk summaries and the final pass's input; its output tokens are
+/// counted by the caller, see [`LlmSummarizer::count_tokens`]. Pass the
+/// collected text to [`LlmSummarizer::finish_stream`] for the length
+/// checks streaming can't apply and to cache it.
+pub struct SummaryStream {
+    /// The text the final pass summarizes: the document, or the joined
+    /// summaries of its chunks.
+    pub input: String,
+    pub chunks: usize,
+    pub model: String,
+    pub max_tokens: usize,
+    pub usage: TokenUsage,
+    pub stream: BoxStream<'static, Result<String>>,
+}
+
 pub struct LlmSummarizer {
     provider: Box<dyn LlmProvider + Send + Sync>,
+    model: String,
+    context_size: usize,
     max_tokens: usize,
+    /// Lower bound of the output budget with `adaptive_tokens`; unset
+    /// when every request asks for `max_tokens`.
+    adaptive_min_tokens: Option<usize>,
+    min_tokens: usize,
+    max_summary_chars: Option<usize>,
+    max_input_tokens: usize,
+    chunk_size_tokens: usize,
+    chunk_overlap_tokens: usize,
+    batch_threshold_tokens: usize,
+    batch_size: usize,
+    /// `default.self_check`, retrying up to `max_retries` times.
+    self_check: bool,
+    max_retries: u32,
+    tokens: TokenCounter,
+    /// The configured prompts and temperature, which key the cache along
+    /// with the output budget.
+    prompts: Prompts,
+    temperature: f32,
+    cache: Option<SummaryCache>,
+    /// Serve summaries only from `cache`; see [`LlmSummarizer::offline`].
+    offline: bool,
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize,
+    saved_input_tokens: AtomicUsize,
+    saved_output_tokens: AtomicUsize,
 }
 
 impl LlmSummarizer {
-    pub fn new(provider: Box<dyn LlmProvider + Send + Sync>, max_tokens: usize) -> Self {
-        Self {
+    /// Token limits come from the resolved config. The context window comes
+    /// from `api.context_window` or [`context_window`]. A `max_tokens` that
+    /// leaves no room for input is clamped to half the window.
+    /// `max_input_tokens` defaults to whatever fits alongside `max_tokens` of
+    /// output, and the chunk size defaults to that limit.
+    pub fn new(
+        provider: Box<dyn LlmProvider + Send + Sync>,
+        config: &Config,
+        cache: Option<SummaryCache>,
+    ) -> Result<Self> {
+        let model = &config.default.model;
+        let context_size = config.api.context_window.unwrap_or_else(|| context_window(model));
+        let available = context_size.saturating_sub(PROMPT_OVERHEAD_TOKENS);
+        if available < 2 {
+            anyhow::bail!("The {}-token context window of {} is too small", context_size, model);
+        }
+
+        let mut max_tokens = config.default.max_tokens;
+        if max_tokens >= available {
+            warn!(
+                "max_tokens ({}) leaves no room for input in the {}-token context window of {}; using {}",
+                max_tokens,
+                context_size,
+                model,
+                available / 2
+            );
+            max_tokens = available / 2;
+        }
+
+        let max_input_tokens = config.processing.max_input_tokens
+            .unwrap_or(available - max_tokens)
+            .clamp(1, available - 1);
+
+        let chunk_size_tokens = config.processing.chunk_size_tokens
+            .unwrap_or(max_input_tokens)
+            .clamp(1, max_input_tokens);
+
+        Ok(Self {
             provider,
+            model: model.clone(),
+            context_size,
             max_tokens,
+            adaptive_min_tokens: config.default.adaptive_tokens.then(|| config.default.adaptive_min_tokens.min(max_tokens)),
+            min_tokens: config.default.min_tokens,
+            max_summary_chars: config.default.max_summary_chars,
+            max_input_tokens,
+            chunk_size_tokens,
+            chunk_overlap_tokens: config.processing.chunk_overlap_tokens.min(chunk_size_tokens / 2),
+            batch_threshold_tokens: config.processing.batch_threshold_tokens.min(chunk_size_tokens),
+            batch_size: config.processing.batch_size.max(1),
+            self_check: config.default.self_check,
+            max_retries: config.api.max_retries,
+            tokens: TokenCounter::for_model(model)?,
+            prompts: Prompts::from_config(&config.api)?,
+            temperature: config.default.temperature,
+            cache,
+            offline: false,
+            cache_hits: AtomicUsize::new(0),
+            cache_misses: AtomicUsize::new(0),
+            saved_input_tokens: AtomicUsize::new(0),
+            saved_output_tokens: AtomicUsize::new(0),
+        })
+    }
+
+    /// A summarizer that never makes API calls: summaries come from
+    /// `cache`, and documents missing from it fail with [`NotCached`]. No
+    /// provider is built, so no API key is needed.
+    pub fn offline(config: &Config, cache: SummaryCache) -> Result<Self> {
+        let mut summarizer = Self::new(Box::new(OfflineProvider), config, Some(cache))?;
+        summarizer.offline = true;
+        Ok(summarizer)
+    }
+
+    /// Output token limit per request, after clamping to the context window.
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// The requests a summary of `content` starts with, as the provider
+    /// would send them: one for a document that fits in a single chunk,
+    /// otherwise one per chunk. The final pass over chunk summaries depends
+    /// on their responses and isn't included. Makes no API calls.
+    pub fn build_prompts(&self, content: &str, format: Option<DocumentFormat>) -> Result<Vec<Vec<Message>>> {
+        let chunks = if self.tokens.count(content) <= self.chunk_size_tokens {
+            vec![content.to_string()]
+        } else {
+            self.tokens.split_into_chunks(content, self.chunk_size_tokens, self.chunk_overlap_tokens)?
+        };
+
+        chunks
+            .iter()
+            .map(|chunk| Ok(self.provider.build_messages(&self.fit_to_context(chunk)?, format)))
+            .collect()
+    }
+
+    /// Whether the provider packs several documents into one request; see
+    /// [`LlmSummarizer::batches`].
+    pub fn supports_batch(&self) -> bool {
+        self.provider.supports_batch()
+    }
+
+    /// Tokens in `text` by the model's tokenizer.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.tokens.count(text)
+    }
+
+    /// Cache hits and misses so far. Each chunk summary is a lookup of its
+    /// own; retries for length or the self-check aren't looked up.
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+            saved: TokenUsage {
+                input_tokens: self.saved_input_tokens.load(Ordering::Relaxed),
+                output_tokens: self.saved_output_tokens.load(Ordering::Relaxed),
+            },
+        }
+    }
+
+    /// `format` selects the system prompt; see [`Prompts::system`].
+    pub async fn summarize(&self, content: &str, format: Option<DocumentFormat>) -> Result<GeneratedSummary, DocTldrError> {
+        Ok(self.summarize_unclassified(content, format).await?)
+    }
+
+    async fn summarize_unclassified(&self, content: &str, format: Option<DocumentFormat>) -> Result<GeneratedSummary> {
+        let (content, chunks, mut usage) = self.prepare(content, format).await?;
+        let result = self.summarize_once(&content, format).await?;
+        let (result, retried) = self.self_check(&content, format, result).await?;
+        let result = self.expand_if_short(&content, format, result).await?;
+        usage += result.usage.unwrap_or_default();
+        Ok(GeneratedSummary {
+            text: self.cap_length(result.text),
+            chunks,
+            model: result.model,
+            usage,
+            retried,
+            max_tokens: self.target_tokens(&content),
+        })
+    }
+
+    pub async fn summarize_stream(&self, content: &str, format: Option<DocumentFormat>) -> Result<SummaryStream> {
+        let (content, chunks, mut usage) = self.prepare(content, format).await?;
+        if let Some(summary) = self.cached(&content, format).await {
+            return Ok(SummaryStream {
+                max_tokens: self.target_tokens(&content),
+                input: content.into_owned(),
+                chunks,
+                model: self.model.clone(),
+                usage,
+                stream: stream::once(async move { Ok(summary) }).boxed(),
+            });
+        }
+
+        // Streamed responses carry no usage, so the input is estimated
+        usage.input_tokens += PROMPT_OVERHEAD_TOKENS + self.tokens.count(&content);
+        let stream = self.provider.summarize_stream(&content, format, self.output_budget(&content)).await?;
+        let max_tokens = self.target_tokens(&content);
+        Ok(SummaryStream {
+            input: content.into_owned(),
+            chunks,
+            model: self.model.clone(),
+            max_tokens,
+            usage,
+            stream,
+        })
+    }
+
+    /// Applies the self-check, minimum length and `max_summary_chars` of
+    /// [`LlmSummarizer::summarize`] to `summary`, streamed from a
+    /// [`SummaryStream`] with `input`. Summaries failing a check are
+    /// requested again without streaming; their usage is added. The checked
+    /// summary is what gets cached.
+    pub async fn finish_stream(&self, input: &str, format: Option<DocumentFormat>, summary: GeneratedSummary) -> Result<GeneratedSummary> {
+        let streamed = SummaryResult {
+            text: summary.text,
+            usage: Some(TokenUsage::default()),
+            model: summary.model,
+        };
+        let (result, retried) = self.self_check(input, format, streamed).await?;
+        let result = self.expand_if_short(input, format, result).await?;
+        self.store(input, format, &result.text).await;
+        let mut usage = summary.usage;
+        usage += result.usage.unwrap_or_default();
+        Ok(GeneratedSummary {
+            text: self.cap_length(result.text),
+            model: result.model,
+            usage,
+            retried,
+            ..summary
+        })
+    }
+
+    /// Groups documents, given as content and format, for
+    /// [`LlmSummarizer::summarize_batch`], as indices into `documents`.
+    /// Documents of at most `batch_threshold_tokens` are packed together
+    /// with others of the same format, up to `batch_size` at a time and as
+    /// many as fit the input limit; every other document gets a group of
+    /// its own.
+    pub fn batches(&self, documents: &[(&str, Option<DocumentFormat>)]) -> Vec<Vec<usize>> {
+        let mut groups = Vec::new();
+        // Open batch and its token count per format
+        let mut open: Vec<(Option<DocumentFormat>, Vec<usize>, usize)> = Vec::new();
+
+        for (index, (content, format)) in documents.iter().enumerate() {
+            let count = self.tokens.count(content);
+            if self.batch_threshold_tokens == 0 || count > self.batch_threshold_tokens {
+                groups.push(vec![index]);
+                continue;
+            }
+            let tokens = count + BATCH_DELIMITER_TOKENS;
+            let position = match open.iter().position(|(open_format, _, _)| open_format == format) {
+                Some(position) => position,
+                None => {
+                    open.push((*format, Vec::new(), 0));
+                    open.len() - 1
+                }
+            };
+            let (_, batch, batch_tokens) = &mut open[position];
+            if !batch.is_empty() && (batch.len() == self.batch_size || *batch_tokens + tokens > self.max_input_tokens) {
+                groups.push(std::mem::take(batch));
+                *batch_tokens = 0;
+            }
+            batch.push(index);
+            *batch_tokens += tokens;
+        }
+        groups.extend(open.into_iter().map(|(_, batch, _)| batch).filter(|batch| !batch.is_empty()));
+
+        groups
+    }
+
+    /// Summarizes a group from [`LlmSummarizer::batches`], returning one
+    /// result per document in order. Documents not in the cache are sent in
+    /// a single request when the provider supports it. If that request
+    /// fails, they are summarized one at a time instead.
+    pub async fn summarize_batch(&self, contents: &[&str], format: Option<DocumentFormat>) -> Vec<Result<GeneratedSummary>> {
+        let mut results: Vec<Option<Result<GeneratedSummary>>> = contents.iter().map(|_| None).collect();
+        let mut pending = Vec::new();
+        // Only documents sent in the batched request count as misses here;
+        // the rest count when summarized one at a time
+        for (index, content) in contents.iter().enumerate() {
+            match self.lookup_single(content, format).await {
+                Some(text) => {
+                    self.record_hit(content, &text);
+                    let hit = SummaryResult {
+                        text,
+                        usage: Some(TokenUsage::default()),
+                        model: self.model.clone(),
+                    };
+                    results[index] = Some(self.check_batched(content, format, hit).await);
+                }
+                None => pending.push(index),
+            }
+        }
+
+        if pending.len() > 1 && !self.offline && self.provider.supports_batch() {
+            let batch: Vec<String> = pending.iter().map(|&index| contents[index].to_string()).collect();
+            match self.request_batch(&batch, format).await {
+                Ok(batched) => {
+                    self.cache_misses.fetch_add(batched.len(), Ordering::Relaxed);
+                    for (&index, result) in pending.iter().zip(batched) {
+                        results[index] = Some(self.finish_batched(contents[index], format, result).await);
+                    }
+                }
+                Err(e) => warn!("Batched request for {} documents failed, summarizing them one at a time: {}", batch.len(), e),
+            }
+        }
+
+        let mut summaries = Vec::with_capacity(contents.len());
+        for (content, result) in contents.iter().zip(results) {
+            summaries.push(match result {
+                Some(result) => result,
+                None => self.summarize_unclassified(content, format).await,
+            });
+        }
+        summaries
+    }
+
+    /// One request for all of `batch`; each summary gets an equal share of
+    /// the output room, up to `max_tokens`.
+    async fn request_batch(&self, batch: &[String], format: Option<DocumentFormat>) -> Result<Vec<SummaryResult>> {
+        let input: usize = batch.iter().map(|content| self.tokens.count(content) + BATCH_DELIMITER_TOKENS).sum();
+        let room = self.context_size.saturating_sub(PROMPT_OVERHEAD_TOKENS + input);
+        let target = batch.iter().map(|content| self.target_tokens(content)).max().unwrap_or(self.max_tokens);
+        let max_tokens = target.min(room / batch.len()).max(1);
+
+        let mut results = self.provider.summarize_batch(batch, format, max_tokens).await?;
+        if results.len() != batch.len() {
+            anyhow::bail!("Got {} summaries for {} documents", results.len(), batch.len());
+        }
+        for (content, result) in batch.iter().zip(&mut results) {
+            self.estimate_usage(content, result);
+        }
+        Ok(results)
+    }
+
+    /// Caches one summary of a batched request and checks it as
+    /// [`LlmSummarizer::check_batched`] does.
+    async fn finish_batched(&self, content: &str, format: Option<DocumentFormat>, result: SummaryResult) -> Result<GeneratedSummary> {
+        self.store(&self.fit_to_context(content)?, format, &result.text).await;
+        self.check_batched(content, format, result).await
+    }
+
+    /// Applies the self-check and length checks of
+    /// [`LlmSummarizer::summarize`] to a summary of `content` that was
+    /// batched or found in the cache.
+    async fn check_batched(&self, content: &str, format: Option<DocumentFormat>, result: SummaryResult) -> Result<GeneratedSummary> {
+        let (result, retried) = self.self_check(content, format, result).await?;
+        let result = self.expand_if_short(content, format, result).await?;
+        Ok(GeneratedSummary {
+            text: self.cap_length(result.text),
+            chunks: 1,
+            model: result.model,
+            usage: result.usage.unwrap_or_default(),
+            retried,
+            max_tokens: self.target_tokens(content),
+        })
+    }
+
+    /// Produces the text for the final request, the number of chunks used
+    /// and the tokens spent getting there. Documents larger than one chunk
+    /// are split, each chunk is summarized, and the joined partial summaries
+    /// become the input of a final summary-of-summaries pass (reduced again
+    /// if still too large).
+    async fn prepare<'a>(&self, content: &'a str, format: Option<DocumentFormat>) -> Result<(Cow<'a, str>, usize, TokenUsage)> {
+        let mut usage = TokenUsage::default();
+        if self.tokens.count(content) <= self.chunk_size_tokens {
+            return Ok((Cow::Borrowed(content), 1, usage));
+        }
+
+        let chunks = self.tokens.split_into_chunks(content, self.chunk_size_tokens, self.chunk_overlap_tokens)?;
+        let chunk_count = chunks.len();
+        debug!("Split document into {} chunks", chunk_count);
+        let mut combined = self.summarize_chunks(chunks, format, &mut usage).await?;
+
+        for _ in 0..MAX_REDUCE_ROUNDS {
+            if self.tokens.count(&combined) <= self.max_input_tokens {
+                return Ok((Cow::Owned(combined), chunk_count, usage));
+            }
+            let chunks = self.tokens.split_into_chunks(&combined, self.chunk_size_tokens, 0)?;
+            combined = self.summarize_chunks(chunks, format, &mut usage).await?;
+        }
+
+        Ok((Cow::Owned(self.fit_to_context(&combined)?.into_owned()), chunk_count, usage))
+    }
+
+    async fn summarize_chunks(&self, chunks: Vec<String>, format: Option<DocumentFormat>, usage: &mut TokenUsage) -> Result<String> {
+        let mut partials = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let result = self.summarize_once(chunk, format).await?;
+            *usage += result.usage.unwrap_or_default();
+            partials.push(result.text);
+        }
+        Ok(partials.join("\n\n"))
+    }
+
+    /// Cache hits report no usage and the configured model, since the cache
+    /// is per model.
+    async fn summarize_once(&self, content: &str, format: Option<DocumentFormat>) -> Result<SummaryResult> {
+        let content = self.fit_to_context(content)?;
+        if let Some(text) = self.cached(&content, format).await {
+            return Ok(SummaryResult {
+                text,
+                usage: Some(TokenUsage::default()),
+                model: self.model.clone(),
+            });
         }
+
+        let result = self.request(&content, format).await?;
+        self.store(&content, format, &result.text).await;
+        Ok(result)
     }
 
-    pub async fn summarize(&self, content: &str) -> Result<String> {
-        self.provider.summarize(content, self.max_tokens).await
+    /// Sends one request. Usage the API didn't report is estimated with the
+    /// tokenizer, so the result always has some.
+    async fn request(&self, content: &str, format: Option<DocumentFormat>) -> Result<SummaryResult> {
+        let mut result = self.provider.summarize(content, format, self.output_budget(content)).await?;
+        self.estimate_usage(content, &mut result);
+        Ok(result)
     }
-} 
\ No newline at end of file
+
+    fn estimate_usage(&self, content: &str, result: &mut SummaryResult) {
+        if result.usage.is_none() {
+            debug!("No token usage in the response; estimating");
+            result.usage = Some(TokenUsage {
+                input_tokens: PROMPT_OVERHEAD_TOKENS + self.tokens.count(content),
+                output_tokens: self.tokens.count(&result.text),
+            });
+        }
+    }
+
+    /// With `self_check` on, requests `summary` again while it is empty, a
+    /// refusal or apology, or implausibly short for a long input, saying
+    /// the document is complete, up to `max_retries` times. Returns the
+    /// first summary that passes, or the last one with a warning, and
+    /// whether any request was repeated. The usage of every request is
+    /// reported.
+    async fn self_check(&self, content: &str, format: Option<DocumentFormat>, summary: SummaryResult) -> Result<(SummaryResult, bool)> {
+        if !self.self_check || self.offline {
+            return Ok((summary, false));
+        }
+
+        let refusal = Regex::new(REFUSAL_PATTERN)?;
+        let content = self.fit_to_context(content)?;
+        let input_tokens = self.tokens.count(&content);
+        let problem = |text: &str| {
+            if text.trim().is_empty() {
+                Some("empty")
+            } else if refusal.is_match(text) {
+                Some("a refusal")
+            } else if input_tokens >= SELF_CHECK_MIN_INPUT_TOKENS && self.tokens.count(text) < MIN_PLAUSIBLE_SUMMARY_TOKENS {
+                Some("implausibly short")
+            } else {
+                None
+            }
+        };
+
+        let mut summary = summary;
+        let mut usage = summary.usage.unwrap_or_default();
+        let mut attempts = 0;
+        while let Some(reason) = problem(&summary.text) {
+            if attempts == self.max_retries {
+                warn!("Summary is still {} after {} retries; keeping it", reason, attempts);
+                break;
+            }
+            attempts += 1;
+            debug!("Summary is {}; asking again ({}/{})", reason, attempts, self.max_retries);
+            let request = format!(
+                "The document below is complete and fine to summarize. Summarize it directly, without apologies, refusals or remarks about the text.\n\n{}",
+                content
+            );
+            match self.request(&request, format).await {
+                Ok(retry) => {
+                    usage += retry.usage.unwrap_or_default();
+                    summary = retry;
+                }
+                Err(e) => {
+                    warn!("Failed to request the summary again: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if attempts > 0 && problem(&summary.text).is_none() {
+            self.store(&content, format, &summary.text).await;
+        }
+        summary.usage = Some(usage);
+        Ok((summary, attempts > 0))
+    }
+
+    /// Asks once for a more detailed summary when `summary` is under
+    /// `min_tokens`. The floor shrinks to half the input for short inputs,
+    /// which can't support a long summary. The longer of the two summaries
+    /// is kept and cached; the usage of both is reported.
+    async fn expand_if_short(&self, content: &str, format: Option<DocumentFormat>, summary: SummaryResult) -> Result<SummaryResult> {
+        let floor = self.min_tokens.min(self.tokens.count(content) / 2);
+        let summary_tokens = self.tokens.count(&summary.text);
+        // A cached summary is the best there is offline
+        if summary_tokens >= floor || self.offline {
+            return Ok(summary);
+        }
+
+        debug!("Summary is {} tokens, under the {}-token floor; asking for more detail", summary_tokens, floor);
+        let content = self.fit_to_context(content)?;
+        let request = format!(
+            "A previous summary of this document was too brief. Write a more detailed summary of at least {} tokens.\n\n{}",
+            floor, content
+        );
+        let expanded = match self.request(&request, format).await {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                warn!("Failed to expand short summary: {}", e);
+                return Ok(summary);
+            }
+        };
+
+        let mut usage = summary.usage.unwrap_or_default();
+        usage += expanded.usage.unwrap_or_default();
+        let mut kept = if self.tokens.count(&expanded.text) <= summary_tokens {
+            summary
+        } else {
+            self.store(&content, format, &expanded.text).await;
+            expanded
+        };
+        kept.usage = Some(usage);
+        Ok(kept)
+    }
+
+    /// Cuts summaries over `max_summary_chars` at the last whitespace
+    /// before the limit, or at the limit itself if there is none.
+    fn cap_length(&self, summary: String) -> String {
+        let Some(max_chars) = self.max_summary_chars else {
+            return summary;
+        };
+        let Some((end, _)) = summary.char_indices().nth(max_chars) else {
+            return summary;
+        };
+
+        debug!("Summary is over {} characters; truncating", max_chars);
+        let cut = &summary[..end];
+        let cut = match cut.rfind(char::is_whitespace) {
+            Some(space) if space > 0 => &cut[..space],
+            _ => cut,
+        };
+        cut.trim_end().to_string()
+    }
+
+    /// Output tokens to request for `content`: `max_tokens`, or less when an
+    /// explicit `max_input_tokens` lets input and output overflow the window.
+    fn output_budget(&self, content: &str) -> usize {
+        let target = self.target_tokens(content);
+        let room = self.context_size.saturating_sub(PROMPT_OVERHEAD_TOKENS + self.tokens.count(content));
+        if room >= target {
+            return target;
+        }
+
+        warn!(
+            "Only {} tokens of the {}-token context window are left for output; lowering max_tokens from {}",
+            room, self.context_size, target
+        );
+        room.max(1)
+    }
+
+    /// The output budget `content` calls for: `max_tokens`, or with
+    /// `adaptive_tokens` a share of its tokens (counted up to the input
+    /// limit) between `adaptive_min_tokens` and `max_tokens`. Also keys the
+    /// cache, since the budget shapes the summary.
+    fn target_tokens(&self, content: &str) -> usize {
+        match self.adaptive_min_tokens {
+            Some(min) => {
+                let input = self.tokens.count(content).min(self.max_input_tokens);
+                (input / ADAPTIVE_TOKENS_DIVISOR).clamp(min, self.max_tokens)
+            }
+            None => self.max_tokens,
+        }
+    }
+
+    /// Truncates content that would overflow the input token limit.
+    fn fit_to_context<'a>(&self, content: &'a str) -> Result<Cow<'a, str>> {
+        let count = self.tokens.count(content);
+        if count <= self.max_input_tokens {
+            return Ok(Cow::Borrowed(content));
+        }
+
+        warn!(
+            "Input is {} tokens, over the {}-token input limit; truncating",
+            count, self.max_input_tokens
+        );
+        Ok(Cow::Owned(self.tokens.truncate(content, self.max_input_tokens)?))
+    }
+
+    /// Looks `content` up in the cache, counting the hit or miss.
+    async fn cached(&self, content: &str, format: Option<DocumentFormat>) -> Option<String> {
+        match self.lookup(content, format).await {
+            Some(summary) => {
+                self.record_hit(content, &summary);
+                Some(summary)
+            }
+            None => {
+                self.cache_misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Looks up a document summarized in a single request, under the input
+    /// [`LlmSummarizer::summarize_once`] caches it by. Longer documents
+    /// are cached chunk by chunk and never found here.
+    async fn lookup_single(&self, content: &str, format: Option<DocumentFormat>) -> Option<String> {
+        if self.tokens.count(content) > self.chunk_size_tokens {
+            return None;
+        }
+        let content = self.fit_to_context(content).ok()?;
+        self.lookup(&content, format).await
+    }
+
+    /// Keyed by the prompts a request for `content` would send, so changing
+    /// the template, the temperature or the format's system prompt misses.
+    async fn lookup(&self, content: &str, format: Option<DocumentFormat>) -> Option<String> {
+        let prompt = self.prompts.user(content);
+        let system = self.prompts.system(format);
+        let summary = self.cache.as_ref()?.get(self.target_tokens(content), self.temperature, system, &prompt).await?;
+        debug!("Summary cache hit");
+        Some(summary)
+    }
+
+    /// Counts a hit, with the tokens a request for it would have used.
+    fn record_hit(&self, content: &str, summary: &str) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        self.saved_input_tokens.fetch_add(PROMPT_OVERHEAD_TOKENS + self.tokens.count(content), Ordering::Relaxed);
+        self.saved_output_tokens.fetch_add(self.tokens.count(summary), Ordering::Relaxed);
+    }
+
+    async fn store(&self, content: &str, format: Option<DocumentFormat>, summary: &str) {
+        if let Some(cache) = &self.cache {
+            let prompt = self.prompts.user(content);
+            let system = self.prompts.system(format);
+            if let Err(e) = cache.put(self.target_tokens(content), self.temperature, system, &prompt, summary).await {
+                warn!("Failed to write summary cache: {}", e);
+            }
+        }
+    }
+}
@@ -1,33 +1,149 @@
-use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
-use tracing::Level;
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use tracing::{info, Level};
 use tracing_subscriber::fmt;
 
-mod config;
-mod processing;
-mod llm;
-mod output;
-
-use processing::DocumentProcessor;
-use llm::{LlmSummarizer, OpenAiProvider};
-use output::{OutputWriter, Summary};
+use doctldr::cache::SummaryCache;
+use doctldr::crawl::CrawlOptions;
+use doctldr::{config, llm, output, processing, RunOptions};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// Input directories to process
-    #[arg(required = true)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, arguments are taken as `summarize` arguments
+    #[command(flatten)]
+    summarize: SummarizeArgs,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // parsed once per process
+enum Command {
+    /// Summarize documentation (the default when no subcommand is given)
+    Summarize(SummarizeArgs),
+    /// Manage the summary cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Inspect configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Remove all cached summaries
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration as TOML
+    Show {
+        /// Config file to read instead of the default
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+    /// Print the default config file path
+    Path,
+}
+
+#[derive(Args)]
+struct SummarizeArgs {
+    /// Input directories to process; `-` reads a single document from stdin,
+    /// `http(s)://` URLs are fetched and git repository URLs are cloned
+    #[arg(required_unless_present_any = ["clear_cache", "list_formats", "list_providers", "crawl", "show_config"])]
     input_dirs: Vec<PathBuf>,
 
+    /// Format of the document read from stdin (md, rst, html, txt, adoc, pdf, docx, ipynb, rs, py)
+    #[arg(long, value_name = "FORMAT", default_value = "md")]
+    stdin_format: String,
+
+    /// Order of summaries in the output: path, size or mtime (ascending)
+    #[arg(long, value_name = "KEY", default_value = "path")]
+    sort: String,
+
+    /// Summarize at most N files, the first ones in --sort order
+    #[arg(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// User-Agent header sent when fetching URL inputs
+    #[arg(long, value_name = "AGENT")]
+    user_agent: Option<String>,
+
+    /// Branch, tag or commit to check out for git repository inputs
+    #[arg(long, visible_alias = "branch", value_name = "REF")]
+    rev: Option<String>,
+
+    /// Crawl the site at URL, summarizing each linked page (repeatable).
+    /// URL inputs given alongside are crawled too
+    #[arg(long, value_name = "URL")]
+    crawl: Vec<String>,
+
+    /// Pages to summarize per crawl at most
+    #[arg(long, value_name = "N", default_value_t = 50, requires = "crawl")]
+    max_pages: usize,
+
+    /// Links to follow away from the crawl's start page
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "crawl")]
+    crawl_depth: usize,
+
+    /// Only follow links to the crawl's start host
+    #[arg(long, requires = "crawl")]
+    same_host: bool,
+
     /// Output file path
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Output format (md, json, txt)
+    /// Also write the summaries as FORMAT to PATH, e.g. json:out.json (repeatable)
+    #[arg(long, value_name = "FORMAT:PATH", value_parser = parse_also)]
+    also: Vec<(String, PathBuf)>,
+
+    /// Write one summary file per input file under DIR, mirroring the input tree
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    split: Option<PathBuf>,
+
+    /// Write one output file per input directory, into the --output directory
+    #[arg(long, conflicts_with = "split")]
+    per_input: bool,
+
+    /// Add summaries to the existing --output file instead of replacing it
+    #[arg(long, requires = "output", conflicts_with = "per_input")]
+    append: bool,
+
+    /// Output format (md, json, jsonl, yaml, toml, txt, html, xml, csv)
     #[arg(short, long)]
     format: Option<String>,
 
+    /// Include per-summary metadata (sizes, compression, tokens) in the
+    /// output (the default)
+    #[arg(long, overrides_with = "no_metadata")]
+    metadata: bool,
+
+    /// Leave per-summary metadata out of the output
+    #[arg(long, overrides_with = "metadata")]
+    no_metadata: bool,
+
+    /// Include each document's preprocessed text in the output, next to its
+    /// summary (can make the output much larger)
+    #[arg(long)]
+    include_original: bool,
+
     /// LLM model to use
     #[arg(long)]
     model: Option<String>,
@@ -36,32 +152,198 @@ struct Cli {
     #[arg(long)]
     max_tokens: Option<usize>,
 
+    /// Scale each summary's token budget with its input, up to --max-tokens
+    #[arg(long)]
+    adaptive_tokens: bool,
+
+    /// Sampling temperature (0.0-2.0)
+    #[arg(long)]
+    temperature: Option<f32>,
+
+    /// Number of documents summarized in parallel
+    #[arg(long, value_name = "N")]
+    concurrency: Option<usize>,
+
+    /// Reproducible output for golden-file tests: temperature 0, one file
+    /// at a time, summaries in path order
+    #[arg(long, conflicts_with_all = ["temperature", "sort"])]
+    deterministic: bool,
+
+    /// Maximum input tokens sent per document; longer inputs are truncated
+    #[arg(long)]
+    max_input_tokens: Option<usize>,
+
+    /// Context window of the model in tokens, overriding the built-in table
+    #[arg(long, value_name = "TOKENS")]
+    model_context_window: Option<usize>,
+
+    /// Read the user prompt template from FILE; `{content}` marks where the
+    /// document goes
+    #[arg(long, value_name = "FILE")]
+    prompt_file: Option<PathBuf>,
+
+    /// Also process files matching GLOB (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Use only the --include patterns, ignoring configured ones
+    #[arg(long, requires = "include")]
+    include_only: bool,
+
+    /// Skip files matching GLOB (repeatable); excludes win over includes
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Skip files larger than this many bytes
+    #[arg(long)]
+    max_file_size: Option<u64>,
+
+    /// Summarize files of at most this many tokens several to a request
+    #[arg(long, value_name = "TOKENS")]
+    batch_threshold: Option<usize>,
+
+    /// Skip files that aren't valid text in any supported encoding
+    #[arg(long)]
+    strict_encoding: bool,
+
+    /// Only process files modified since DATE (RFC 3339 or relative, e.g. 7d)
+    #[arg(long, value_name = "DATE")]
+    since: Option<String>,
+
+    /// Only process files changed relative to a git ref
+    #[arg(long, value_name = "REF")]
+    since_git: Option<String>,
+
+    /// Only summarize files that are new or changed since an earlier JSON output, and list removed ones
+    #[arg(long, value_name = "PREVIOUS_OUTPUT")]
+    diff: Option<PathBuf>,
+
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
 
+    /// Only report errors; no warnings, info lines or progress bars
+    #[arg(short, long, conflicts_with_all = ["verbose", "debug"])]
+    quiet: bool,
+
     /// Custom config file path
     #[arg(short, long)]
     config: Option<PathBuf>,
 
+    /// Stop at the first file that can't be read or summarized
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Skip files that can't be read or summarized, exiting non-zero at the
+    /// end if any failed (the default)
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Pipe each summary through CMD (run by the shell) and use its output
+    #[arg(long, value_name = "CMD")]
+    post_process: Option<String>,
+
+    /// Pick which matched files to summarize before any API calls
+    #[arg(short, long)]
+    interactive: bool,
+
     /// Process without generating output
     #[arg(long)]
     dry_run: bool,
 
+    /// Write the prompts each file would be sent with under DIR, without
+    /// calling the API
+    #[arg(long, value_name = "DIR")]
+    dump_prompts: Option<PathBuf>,
+
     /// Enable debug logging
     #[arg(long)]
     debug: bool,
+
+    /// Produce one combined summary per input directory
+    #[arg(long)]
+    merge: bool,
+
+    /// Always call the LLM, bypassing the summary cache
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Use only cached summaries: never call the API or fetch inputs, and
+    /// fail files whose summary isn't cached
+    #[arg(long, conflicts_with = "no_cache")]
+    offline: bool,
+
+    /// Print a report of files, sizes, tokens and time at the end
+    #[arg(long)]
+    stats: bool,
+
+    /// Remove all cached summaries before running
+    #[arg(long)]
+    clear_cache: bool,
+
+    /// List supported output formats and exit
+    #[arg(long)]
+    list_formats: bool,
+
+    /// List supported LLM providers and exit
+    #[arg(long)]
+    list_providers: bool,
+
+    /// Print the configuration after applying the config file and the
+    /// other command-line options, as TOML, and exit
+    #[arg(long)]
+    show_config: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Summarize(args)) => summarize(args).await,
+        None => summarize(cli.summarize).await,
+        Some(Command::Cache { action: CacheCommand::Clear }) => {
+            SummaryCache::clear(&SummaryCache::default_dir()?).await?;
+            println!("Cleared summary cache");
+            Ok(())
+        }
+        Some(Command::Config { action: ConfigCommand::Show { config: path } }) => {
+            show_config(&load_config(path.as_deref())?, path.as_deref())
+        }
+        Some(Command::Config { action: ConfigCommand::Path }) => {
+            println!("{}", config::Config::default_path()?.display());
+            Ok(())
+        }
+    }
+}
+
+async fn summarize(args: SummarizeArgs) -> Result<()> {
+    if args.list_formats || args.list_providers {
+        if args.list_formats {
+            for format in output::FORMATS {
+                let aliases = if format.aliases.is_empty() {
+                    String::new()
+                } else {
+                    format!(" (alias: {})", format.aliases.join(", "))
+                };
+                println!("{:<6} {}{}", format.name, format.description, aliases);
+            }
+        }
+        if args.list_providers {
+            for provider in llm::PROVIDERS {
+                println!("{}", provider);
+            }
+        }
+        return Ok(());
+    }
+
     // Initialize logging
-    let log_level = if cli.debug {
+    let log_level = if args.debug {
         Level::DEBUG
-    } else if cli.verbose {
+    } else if args.verbose {
         Level::INFO
+    } else if args.quiet {
+        Level::ERROR
     } else {
         Level::WARN
     };
@@ -77,59 +359,155 @@ async fn main() -> Result<()> {
         .compact()
         .init();
 
-    // Load configuration
-    let mut config = config::Config::load()?;
+    let mut config = load_config(args.config.as_deref())?;
 
     // Override config with CLI arguments
-    if let Some(model) = cli.model {
+    if let Some(model) = args.model {
         config.default.model = model;
     }
-    if let Some(max_tokens) = cli.max_tokens {
+    if let Some(max_tokens) = args.max_tokens {
         config.default.max_tokens = max_tokens;
     }
-    if let Some(format) = cli.format.clone() {
+    if args.adaptive_tokens {
+        config.default.adaptive_tokens = true;
+    }
+    if let Some(temperature) = args.temperature {
+        config.default.temperature = temperature;
+    }
+    if let Some(concurrency) = args.concurrency {
+        config.processing.llm_concurrency = concurrency;
+    }
+    if args.deterministic {
+        config.make_deterministic();
+    }
+    if let Some(max_input_tokens) = args.max_input_tokens {
+        config.processing.max_input_tokens = Some(max_input_tokens);
+    }
+    if let Some(context_window) = args.model_context_window {
+        config.api.context_window = Some(context_window);
+    }
+    if let Some(prompt_file) = args.prompt_file.clone() {
+        config.api.prompt_file = Some(prompt_file);
+    }
+    if args.include_only {
+        config.processing.include_patterns.clear();
+    }
+    config.processing.include_patterns.extend(args.include.iter().cloned());
+    config.processing.exclude_patterns.extend(args.exclude.iter().cloned());
+    if let Some(max_file_size) = args.max_file_size {
+        config.processing.max_file_size_bytes = max_file_size;
+    }
+    if let Some(threshold) = args.batch_threshold {
+        config.processing.batch_threshold_tokens = threshold;
+    }
+    if args.strict_encoding {
+        config.processing.strict_encoding = true;
+    }
+    if let Some(format) = args.format.clone() {
         config.default.format = format;
     }
-    config.default.verbose = cli.verbose;
-
-    // Initialize components
-    let document_processor = DocumentProcessor::new(
-        config.processing.max_depth,
-        config.processing.include_patterns.clone(),
-        config.processing.exclude_patterns.clone(),
-    );
-
-    let llm_provider = OpenAiProvider::new(config.default.model.clone())?;
-    let summarizer = LlmSummarizer::new(
-        Box::new(llm_provider),
-        config.default.max_tokens,
-    );
+    if args.metadata {
+        config.output.include_metadata = true;
+    }
+    if args.no_metadata {
+        config.output.include_metadata = false;
+    }
+    config.default.verbose = args.verbose;
+    if args.merge {
+        config.output.merge = true;
+    }
+    config.validate()?;
 
-    let output_writer = OutputWriter::new(&config.default.format)?;
+    if args.show_config {
+        return show_config(&config, args.config.as_deref());
+    }
 
-    // Process each input directory
-    let mut all_summaries = Vec::new();
+    if args.clear_cache {
+        SummaryCache::clear(&SummaryCache::default_dir()?).await?;
+        info!("Cleared summary cache");
+        if args.input_dirs.is_empty() && args.crawl.is_empty() {
+            return Ok(());
+        }
+    }
 
-    for dir in cli.input_dirs {
-        // Process documents
-        let documents = document_processor.process_directory(&dir).await?;
+    // Progress bars would clobber log lines and pollute piped output
+    let show_progress = !args.verbose && !args.debug && !args.quiet && io::stdout().is_terminal();
+    // Without a terminal to prompt on, every matched file is summarized
+    let interactive = args.interactive && io::stdin().is_terminal() && io::stdout().is_terminal();
 
-        // Generate summaries
-        for document in documents {
-            if cli.dry_run {
-                println!("Would process: {}", document.path.display());
-                continue;
-            }
+    let options = RunOptions {
+        output: args.output,
+        split: args.split,
+        also: args.also,
+        per_input: args.per_input,
+        append: args.append,
+        stdin_format: args.stdin_format,
+        sort: args.sort,
+        max_files: args.max_files,
+        user_agent: args.user_agent,
+        git_rev: args.rev,
+        crawl: (!args.crawl.is_empty()).then_some(CrawlOptions {
+            max_pages: args.max_pages,
+            max_depth: args.crawl_depth,
+            same_host: args.same_host,
+        }),
+        modified_since: args.since.as_deref().map(processing::parse_since).transpose()?,
+        since_git: args.since_git,
+        diff: args.diff,
+        dry_run: args.dry_run,
+        dump_prompts: args.dump_prompts,
+        no_cache: args.no_cache,
+        offline: args.offline,
+        stats: args.stats,
+        post_process: args.post_process,
+        include_original: args.include_original,
+        fail_fast: args.fail_fast,
+        interactive,
+        stream_to_stderr: args.verbose,
+        show_progress,
+    };
 
-            let summary = summarizer.summarize(&document.content).await?;
-            all_summaries.push(Summary::new(&document, summary));
-        }
+    let mut inputs = args.input_dirs;
+    inputs.extend(args.crawl.iter().map(PathBuf::from));
+    let outcome = doctldr::run(&config, &inputs, &options).await?;
+    if outcome.files_failed > 0 {
+        anyhow::bail!("{} of {} files failed", outcome.files_failed, outcome.files_failed + outcome.files_processed);
     }
+    Ok(())
+}
 
-    // Write output
-    if !cli.dry_run {
-        output_writer.write(all_summaries, cli.output.as_deref()).await?;
+/// Splits an `--also` value at its first `:` into a format and a path.
+fn parse_also(spec: &str) -> Result<(String, PathBuf), String> {
+    match spec.split_once(':') {
+        Some((format, path)) if !format.is_empty() && !path.is_empty() => Ok((format.to_string(), PathBuf::from(path))),
+        _ => Err(format!("expected FORMAT:PATH, e.g. json:summary.json, got {}", spec)),
     }
+}
 
+/// Prints `config` as TOML, headed by the file it was loaded from. The
+/// config holds no secrets; API keys are only referenced by `key_env`.
+fn show_config(config: &config::Config, path: Option<&Path>) -> Result<()> {
+    let source = match path {
+        Some(path) => path.display().to_string(),
+        None => {
+            let default = config::Config::default_path()?;
+            if default.exists() {
+                default.display().to_string()
+            } else {
+                format!("none ({} not found, using defaults)", default.display())
+            }
+        }
+    };
+    println!("# Config file: {}", source);
+    print!("{}", toml::to_string_pretty(config).context("Failed to serialize config")?);
     Ok(())
 }
+
+/// Loads the config file given with `--config`, or the default one.
+fn load_config(path: Option<&Path>) -> Result<config::Config> {
+    Ok(match path {
+        Some(path) => config::Config::load_from(path)
+            .with_context(|| format!("Failed to load config from {}", path.display()))?,
+        None => config::Config::load()?,
+    })
+}
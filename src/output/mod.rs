@@ -1,40 +1,223 @@
 use anyhow::{Result, Context};
-use serde::Serialize;
-use std::path::Path;
+use pulldown_cmark::{html, Event, Parser};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tracing::info;
 
+use crate::llm::GeneratedSummary;
 use crate::processing::Document;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Summary {
     pub original_path: String,
+    /// `original_path` relative to its input directory, when it came from
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
     pub summary: String,
+    /// Missing when the output was written with `include_metadata` off.
+    #[serde(default)]
     pub metadata: SummaryMetadata,
+    /// The preprocessed text that was summarized; only set with
+    /// `--include-original`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_content: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SummaryMetadata {
     pub original_size: u64,
     pub summary_size: u64,
+    /// `summary_size / original_size`. Empty originals report `1.0` (no
+    /// compression) rather than an infinite or NaN ratio.
     pub compression_ratio: f64,
+    /// Number of chunks the document was split into before summarizing.
+    pub chunks: usize,
+    pub word_count: usize,
+    /// ISO 639-3 code of the original, if detected.
+    pub language: Option<String>,
+    /// Model that wrote the summary, as reported by the API.
+    #[serde(default)]
+    pub model: String,
+    /// Tokens consumed summarizing this document, chunks and retries
+    /// included. Estimated when the API doesn't report them; zero when the
+    /// summary came from the cache.
+    #[serde(default)]
+    pub input_tokens: usize,
+    #[serde(default)]
+    pub output_tokens: usize,
+    /// Output token budget the summary was requested with.
+    #[serde(default)]
+    pub max_tokens: usize,
+    /// `title` from the original's front matter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// `tags` from the original's front matter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// [`Document::content_hash`] of the original. Empty in output written
+    /// before it was recorded.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content_hash: String,
+    /// Whether the summary was requested again for failing the self-check.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retried: bool,
+}
+
+/// Totals for one run, reported with `--stats`.
+#[derive(Debug, Default, Serialize)]
+pub struct RunStats {
+    pub files_processed: usize,
+    pub files_skipped: usize,
+    pub files_failed: usize,
+    pub input_bytes: u64,
+    pub summary_bytes: u64,
+    pub average_compression_ratio: f64,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Summary requests served from the cache and sent to the API.
+    pub cached: usize,
+    pub generated: usize,
+    /// Estimated USD the cache hits would have cost at the configured prices.
+    pub cost_saved: f64,
+    pub elapsed_secs: f64,
+}
+
+impl RunStats {
+    /// Renders as JSON for the json and jsonl formats, otherwise as text.
+    pub fn render(&self, format: &str) -> Result<String> {
+        match format.to_lowercase().as_str() {
+            "json" => serde_json::to_string_pretty(self).context("Failed to serialize to JSON"),
+            "jsonl" | "ndjson" => serde_json::to_string(self).context("Failed to serialize to JSON"),
+            _ => Ok(format!(
+                "Files: {} processed, {} skipped, {} failed\n\
+                 Size: {} bytes in, {} bytes out (average compression {:.1}%)\n\
+                 Tokens: {} in, {} out\n\
+                 Cache: cached: {}, generated: {}, cost saved: ${:.2}\n\
+                 Elapsed: {:.1}s",
+                self.files_processed,
+                self.files_skipped,
+                self.files_failed,
+                self.input_bytes,
+                self.summary_bytes,
+                self.average_compression_ratio * 100.0,
+                self.input_tokens,
+                self.output_tokens,
+                self.cached,
+                self.generated,
+                self.cost_saved,
+                self.elapsed_secs,
+            )),
+        }
+    }
 }
 
 pub trait OutputFormatter {
     fn format(&self, summaries: &[Summary]) -> Result<String>;
+
+    /// Whether formatting summaries one at a time and concatenating the
+    /// results yields a valid document, so output can be appended as each
+    /// summary completes.
+    fn appendable(&self) -> bool {
+        false
+    }
+
+    /// Reads back summaries written by this formatter, so `--append` can
+    /// merge into formats that can't simply be appended to.
+    fn parse(&self, _content: &str) -> Result<Vec<Summary>> {
+        anyhow::bail!("This output format can't be appended to")
+    }
 }
 
-pub struct MarkdownFormatter;
+/// Reads the summaries of an earlier run's JSON or JSON Lines output.
+pub async fn read_summaries(path: &Path) -> Result<Vec<Summary>> {
+    let content = fs::read_to_string(path).await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let parsed = if content.trim_start().starts_with('[') {
+        serde_json::from_str(&content).map_err(anyhow::Error::from)
+    } else {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(anyhow::Error::from))
+            .collect()
+    };
+    parsed.with_context(|| format!("{} is not JSON or JSON Lines summary output", path.display()))
+}
+
+/// A summary as the structured formats serialize it, with `metadata` left
+/// out when `include_metadata` is off.
+#[derive(Serialize)]
+struct SummaryEntry<'a> {
+    original_path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_path: Option<&'a str>,
+    summary: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a SummaryMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_content: Option<&'a str>,
+}
+
+fn entries(summaries: &[Summary], include_metadata: bool) -> Vec<SummaryEntry<'_>> {
+    summaries
+        .iter()
+        .map(|summary| SummaryEntry {
+            original_path: &summary.original_path,
+            relative_path: summary.relative_path.as_deref(),
+            summary: &summary.summary,
+            metadata: include_metadata.then_some(&summary.metadata),
+            original_content: summary.original_content.as_deref(),
+        })
+        .collect()
+}
+
+pub struct MarkdownFormatter {
+    pub include_metadata: bool,
+    /// Put summaries under a `## dir/` heading per top-level directory,
+    /// after those of files directly in an input directory.
+    pub group_by_directory: bool,
+    /// Heading line of each summary; `{path}` is its original path and
+    /// `{number}` its position in the output, from 1.
+    pub heading_template: String,
+    /// Written after each summary's text.
+    pub separator: String,
+}
 impl OutputFormatter for MarkdownFormatter {
     fn format(&self, summaries: &[Summary]) -> Result<String> {
         let mut output = String::new();
+        let mut ordered: Vec<&Summary> = summaries.iter().collect();
+        if self.group_by_directory {
+            // Stable, so each group keeps the sort order
+            ordered.sort_by_key(|summary| top_level_dir(summary));
+        }
 
-        for summary in summaries {
-            output.push_str(&format!("# Summary of {}\n\n", summary.original_path));
+        let mut group = None;
+        for (index, summary) in ordered.into_iter().enumerate() {
+            if self.group_by_directory {
+                let dir = top_level_dir(summary);
+                if let Some(dir) = dir.filter(|_| dir != group) {
+                    output.push_str(&format!("## {}/\n\n", dir));
+                }
+                group = dir;
+            }
+            output.push_str(&heading(&self.heading_template, index + 1, summary));
+            if self.include_metadata {
+                if let Some(title) = &summary.metadata.title {
+                    output.push_str(&format!("**Title:** {}\n\n", title));
+                }
+                if !summary.metadata.tags.is_empty() {
+                    output.push_str(&format!("**Tags:** {}\n\n", summary.metadata.tags.join(", ")));
+                }
+            }
             output.push_str(&summary.summary);
-            output.push_str("\n\n---\n\n");
+            output.push_str(&self.separator);
             
-            if summary.metadata.compression_ratio < 1.0 {
+            if self.include_metadata && summary.metadata.compression_ratio < 1.0 {
                 output.push_str(&format!(
                     "_Compressed to {:.1}% of original size_\n\n",
                     summary.metadata.compression_ratio * 100.0
@@ -44,74 +227,741 @@ impl OutputFormatter for MarkdownFormatter {
 
         Ok(output)
     }
+
+    /// Numbers would restart at 1 in each appended part, and groups would
+    /// be split.
+    fn appendable(&self) -> bool {
+        !self.heading_template.contains("{number}") && !self.group_by_directory
+    }
+}
+
+/// First directory of `summary`'s `relative_path`, or None for files
+/// directly in an input directory and summaries without one.
+fn top_level_dir(summary: &Summary) -> Option<&str> {
+    let relative = Path::new(summary.relative_path.as_deref()?);
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(dir)), Some(_)) => dir.to_str(),
+        _ => None,
+    }
 }
 
-pub struct JsonFormatter;
+pub struct JsonFormatter {
+    pub include_metadata: bool,
+}
 impl OutputFormatter for JsonFormatter {
     fn format(&self, summaries: &[Summary]) -> Result<String> {
-        serde_json::to_string_pretty(summaries).context("Failed to serialize to JSON")
+        serde_json::to_string_pretty(&entries(summaries, self.include_metadata)).context("Failed to serialize to JSON")
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<Summary>> {
+        serde_json::from_str(content).context("Failed to parse existing JSON output")
     }
 }
 
-pub struct PlainTextFormatter;
-impl OutputFormatter for PlainTextFormatter {
+/// One compact JSON object per line, for log and ETL pipelines.
+pub struct JsonLinesFormatter {
+    pub include_metadata: bool,
+}
+impl OutputFormatter for JsonLinesFormatter {
     fn format(&self, summaries: &[Summary]) -> Result<String> {
         let mut output = String::new();
 
+        for entry in entries(summaries, self.include_metadata) {
+            output.push_str(&serde_json::to_string(&entry).context("Failed to serialize to JSON")?);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    fn appendable(&self) -> bool {
+        true
+    }
+}
+
+pub struct YamlFormatter {
+    pub include_metadata: bool,
+}
+impl OutputFormatter for YamlFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        serde_yaml::to_string(&entries(summaries, self.include_metadata)).context("Failed to serialize to YAML")
+    }
+
+    fn appendable(&self) -> bool {
+        true
+    }
+}
+
+/// TOML has no top-level arrays, so summaries become `[[summary]]` tables.
+pub struct TomlFormatter {
+    pub include_metadata: bool,
+}
+impl OutputFormatter for TomlFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        #[derive(Serialize)]
+        struct TomlDocument<'a> {
+            summary: Vec<SummaryEntry<'a>>,
+        }
+
+        let document = TomlDocument { summary: entries(summaries, self.include_metadata) };
+        toml::to_string(&document).context("Failed to serialize to TOML")
+    }
+
+    fn appendable(&self) -> bool {
+        true
+    }
+}
+
+/// A header row and one row per summary, for spreadsheets. Summaries keep
+/// their newlines inside quoted fields. Without metadata, only `path` and
+/// `summary` are written.
+pub struct CsvFormatter {
+    pub include_metadata: bool,
+}
+impl OutputFormatter for CsvFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        if self.include_metadata {
+            writer.write_record(["path", "original_size", "summary_size", "compression_ratio", "summary"])?;
+        } else {
+            writer.write_record(["path", "summary"])?;
+        }
+
         for summary in summaries {
-            output.push_str(&format!("=== {} ===\n\n", summary.original_path));
+            let metadata = &summary.metadata;
+            if self.include_metadata {
+                writer.write_record([
+                    summary.original_path.as_str(),
+                    &metadata.original_size.to_string(),
+                    &metadata.summary_size.to_string(),
+                    &metadata.compression_ratio.to_string(),
+                    &summary.summary,
+                ])?;
+            } else {
+                writer.write_record([summary.original_path.as_str(), &summary.summary])?;
+            }
+        }
+
+        let bytes = writer.into_inner().context("Failed to write CSV")?;
+        String::from_utf8(bytes).context("CSV output is not UTF-8")
+    }
+
+    /// Restores the columns the CSV holds; other metadata is left at its
+    /// defaults.
+    fn parse(&self, content: &str) -> Result<Vec<Summary>> {
+        #[derive(Deserialize)]
+        struct Row {
+            path: String,
+            summary: String,
+            #[serde(default)]
+            original_size: u64,
+            #[serde(default)]
+            summary_size: u64,
+            #[serde(default)]
+            compression_ratio: f64,
+        }
+
+        csv::Reader::from_reader(content.as_bytes())
+            .deserialize()
+            .map(|row| {
+                let row: Row = row.context("Failed to parse existing CSV output")?;
+                Ok(Summary {
+                    original_path: row.path,
+                    relative_path: None,
+                    summary: row.summary,
+                    metadata: SummaryMetadata {
+                        original_size: row.original_size,
+                        summary_size: row.summary_size,
+                        compression_ratio: row.compression_ratio,
+                        ..Default::default()
+                    },
+                    original_content: None,
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct PlainTextFormatter {
+    /// Heading line of each summary; `{path}` is its original path and
+    /// `{number}` its position in the output, from 1.
+    pub heading_template: String,
+    /// Written after each summary's text.
+    pub separator: String,
+}
+impl OutputFormatter for PlainTextFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        let mut output = String::new();
+
+        for (index, summary) in summaries.iter().enumerate() {
+            output.push_str(&heading(&self.heading_template, index + 1, summary));
             output.push_str(&summary.summary);
-            output.push_str("\n\n");
+            output.push_str(&self.separator);
         }
 
         Ok(output)
     }
+
+    /// Numbers would restart at 1 in each appended part.
+    fn appendable(&self) -> bool {
+        !self.heading_template.contains("{number}")
+    }
+}
+
+/// The heading of the `number`th summary from `template`, followed by a
+/// blank line.
+fn heading(template: &str, number: usize, summary: &Summary) -> String {
+    let heading = template.replace("{number}", &number.to_string()).replace("{path}", &summary.original_path);
+    format!("{}\n\n", heading)
+}
+
+pub struct HtmlFormatter {
+    pub include_metadata: bool,
+    /// Prefix each heading with the summary's position, from 1.
+    pub numbered: bool,
+}
+impl OutputFormatter for HtmlFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        let mut output = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Documentation Summaries</title>\n</head>\n<body>\n",
+        );
+
+        for (index, summary) in summaries.iter().enumerate() {
+            output.push_str("<section>\n");
+            let number = if self.numbered { format!("{}. ", index + 1) } else { String::new() };
+            output.push_str(&format!("<h1>{}Summary of {}</h1>\n", number, escape_html(&summary.original_path)));
+
+            // Raw HTML in the summary is rendered as text so it can't break the page
+            let parser = Parser::new(&summary.summary).map(|event| match event {
+                Event::Html(raw) => Event::Text(raw),
+                other => other,
+            });
+            html::push_html(&mut output, parser);
+
+            if self.include_metadata {
+                output.push_str(&format!(
+                    "<footer>Compressed to {:.1}% of original size</footer>\n",
+                    summary.metadata.compression_ratio * 100.0
+                ));
+            }
+            output.push_str("</section>\n");
+        }
+
+        output.push_str("</body>\n</html>\n");
+        Ok(output)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `<summaries>` with one `<summary>` per document. The summary text is
+/// kept verbatim in CDATA since it is usually markdown.
+pub struct XmlFormatter {
+    pub include_metadata: bool,
+}
+impl OutputFormatter for XmlFormatter {
+    fn format(&self, summaries: &[Summary]) -> Result<String> {
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<summaries>\n");
+
+        for summary in summaries {
+            let metadata = &summary.metadata;
+            output.push_str("  <summary>\n");
+            output.push_str(&format!("    <path>{}</path>\n", escape_html(&xml_chars(&summary.original_path))));
+            output.push_str(&format!("    <text>{}</text>\n", cdata(&xml_chars(&summary.summary))));
+            if !self.include_metadata {
+                output.push_str("  </summary>\n");
+                continue;
+            }
+            output.push_str("    <metadata>\n");
+            output.push_str(&format!("      <original_size>{}</original_size>\n", metadata.original_size));
+            output.push_str(&format!("      <summary_size>{}</summary_size>\n", metadata.summary_size));
+            output.push_str(&format!("      <compression_ratio>{}</compression_ratio>\n", metadata.compression_ratio));
+            output.push_str(&format!("      <chunks>{}</chunks>\n", metadata.chunks));
+            output.push_str(&format!("      <word_count>{}</word_count>\n", metadata.word_count));
+            if let Some(language) = &metadata.language {
+                output.push_str(&format!("      <language>{}</language>\n", escape_html(language)));
+            }
+            output.push_str(&format!("      <model>{}</model>\n", escape_html(&xml_chars(&metadata.model))));
+            output.push_str(&format!("      <input_tokens>{}</input_tokens>\n", metadata.input_tokens));
+            output.push_str(&format!("      <output_tokens>{}</output_tokens>\n", metadata.output_tokens));
+            output.push_str(&format!("      <max_tokens>{}</max_tokens>\n", metadata.max_tokens));
+            if let Some(title) = &metadata.title {
+                output.push_str(&format!("      <title>{}</title>\n", escape_html(&xml_chars(title))));
+            }
+            if !metadata.tags.is_empty() {
+                output.push_str("      <tags>\n");
+                for tag in &metadata.tags {
+                    output.push_str(&format!("        <tag>{}</tag>\n", escape_html(&xml_chars(tag))));
+                }
+                output.push_str("      </tags>\n");
+            }
+            if metadata.retried {
+                output.push_str("      <retried>true</retried>\n");
+            }
+            if !metadata.content_hash.is_empty() {
+                output.push_str(&format!("      <content_hash>{}</content_hash>\n", metadata.content_hash));
+            }
+            output.push_str("    </metadata>\n");
+            output.push_str("  </summary>\n");
+        }
+
+        output.push_str("</summaries>\n");
+        Ok(output)
+    }
+}
+
+/// Wraps `text` in CDATA, splitting any `]]>` across two sections.
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Drops the control characters XML 1.0 doesn't allow, even escaped.
+fn xml_chars(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(c, '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}'))
+        .collect()
+}
+
+/// An output format accepted by `--format`.
+pub struct FormatSpec {
+    /// Canonical name, also used as the file extension.
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    build: fn(&FormatOptions) -> Box<dyn OutputFormatter>,
+}
+
+/// Settings from the `[output]` config that shape formatted summaries.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub include_metadata: bool,
+    /// Number the summaries in order in Markdown, plain text and HTML
+    /// headings. Custom `heading_template`s place the number with
+    /// `{number}` instead.
+    pub numbered: bool,
+    /// Group Markdown summaries by the top-level directory of their
+    /// `relative_path`.
+    pub group_by_directory: bool,
+    /// Replaces the heading of each summary in Markdown and plain text
+    /// output; `{path}` is its original path and `{number}` its position.
+    pub heading_template: Option<String>,
+    /// Replaces what Markdown and plain text output write after each
+    /// summary.
+    pub separator: Option<String>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            include_metadata: true,
+            numbered: false,
+            group_by_directory: false,
+            heading_template: None,
+            separator: None,
+        }
+    }
+}
+
+/// Every supported output format; `--format` and `--list-formats` both
+/// read from here.
+pub const FORMATS: &[FormatSpec] = &[
+    FormatSpec {
+        name: "md",
+        aliases: &["markdown"],
+        description: "Markdown with headers and metadata",
+        build: |options| Box::new(MarkdownFormatter {
+            include_metadata: options.include_metadata,
+            group_by_directory: options.group_by_directory,
+            heading_template: options.heading_template.clone().unwrap_or_else(|| {
+                let number = if options.numbered { "{number}. " } else { "" };
+                format!("# {}Summary of {{path}}", number)
+            }),
+            separator: options.separator.clone().unwrap_or_else(|| "\n\n---\n\n".to_string()),
+        }),
+    },
+    FormatSpec {
+        name: "json",
+        aliases: &[],
+        description: "Structured JSON with full metadata",
+        build: |options| Box::new(JsonFormatter { include_metadata: options.include_metadata }),
+    },
+    FormatSpec {
+        name: "jsonl",
+        aliases: &["ndjson"],
+        description: "One compact JSON object per line",
+        build: |options| Box::new(JsonLinesFormatter { include_metadata: options.include_metadata }),
+    },
+    FormatSpec {
+        name: "yaml",
+        aliases: &["yml"],
+        description: "Structured YAML with full metadata",
+        build: |options| Box::new(YamlFormatter { include_metadata: options.include_metadata }),
+    },
+    FormatSpec {
+        name: "toml",
+        aliases: &[],
+        description: "TOML with one [[summary]] table per summary",
+        build: |options| Box::new(TomlFormatter { include_metadata: options.include_metadata }),
+    },
+    FormatSpec {
+        name: "txt",
+        aliases: &["text"],
+        description: "Plain text with minimal formatting",
+        build: |options| Box::new(PlainTextFormatter {
+            heading_template: options.heading_template.clone().unwrap_or_else(|| {
+                let number = if options.numbered { "{number}. " } else { "" };
+                format!("=== {}{{path}} ===", number)
+            }),
+            separator: options.separator.clone().unwrap_or_else(|| "\n\n".to_string()),
+        }),
+    },
+    FormatSpec {
+        name: "html",
+        aliases: &["htm"],
+        description: "Standalone HTML page, one section per summary",
+        build: |options| Box::new(HtmlFormatter {
+            include_metadata: options.include_metadata,
+            numbered: options.numbered,
+        }),
+    },
+    FormatSpec {
+        name: "xml",
+        aliases: &[],
+        description: "XML document, one <summary> element per summary",
+        build: |options| Box::new(XmlFormatter { include_metadata: options.include_metadata }),
+    },
+    FormatSpec {
+        name: "csv",
+        aliases: &[],
+        description: "CSV with a header row, one row per summary",
+        build: |options| Box::new(CsvFormatter { include_metadata: options.include_metadata }),
+    },
+];
+
+/// Placeholders recognized in an output path template.
+const TEMPLATE_PLACEHOLDERS: &[&str] = &["{stem}", "{ext}", "{parent}", "{date}", "{model}"];
+
+/// Whether `path` contains any output path template placeholder.
+pub fn is_template(path: &str) -> bool {
+    TEMPLATE_PLACEHOLDERS.iter().any(|placeholder| path.contains(placeholder))
 }
 
 pub struct OutputWriter {
     formatter: Box<dyn OutputFormatter>,
+    /// File extension for the format, used to name `--split` output files.
+    extension: &'static str,
 }
 
 impl OutputWriter {
     pub fn new(format: &str) -> Result<Self> {
-        let formatter: Box<dyn OutputFormatter> = match format.to_lowercase().as_str() {
-            "md" | "markdown" => Box::new(MarkdownFormatter),
-            "json" => Box::new(JsonFormatter),
-            "txt" | "text" => Box::new(PlainTextFormatter),
-            _ => anyhow::bail!("Unsupported output format: {}", format),
-        };
+        Self::with_metadata(format, true)
+    }
 
-        Ok(Self { formatter })
+    /// Like [`OutputWriter::new`], leaving per-summary metadata out of the
+    /// output when `include_metadata` is false.
+    pub fn with_metadata(format: &str, include_metadata: bool) -> Result<Self> {
+        Self::with_options(format, &FormatOptions { include_metadata, ..Default::default() })
     }
 
-    pub async fn write(&self, summaries: Vec<Summary>, output_path: Option<&Path>) -> Result<()> {
-        let formatted = self.formatter.format(&summaries)?;
+    pub fn with_options(format: &str, options: &FormatOptions) -> Result<Self> {
+        let format = format.to_lowercase();
+        let spec = FORMATS.iter()
+            .find(|spec| spec.name == format || spec.aliases.contains(&format.as_str()))
+            .ok_or_else(|| anyhow::anyhow!("Unsupported output format: {}", format))?;
+        let formatter = (spec.build)(options);
+        let extension = spec.name;
+
+        Ok(Self { formatter, extension })
+    }
 
+    pub async fn write(&self, summaries: Vec<Summary>, output_path: Option<&Path>) -> Result<()> {
         match output_path {
-            Some(path) => {
-                fs::write(path, formatted).await?;
-                info!("Written output to {}", path.display());
+            Some(path) => self.write_file(&summaries, path).await?,
+            None => println!("{}", self.formatter.format(&summaries)?),
+        }
+
+        Ok(())
+    }
+
+    /// Writes `summaries` to `path` without taking them, so the same run
+    /// can write several formats.
+    pub async fn write_file(&self, summaries: &[Summary], path: &Path) -> Result<()> {
+        let formatted = self.formatter.format(summaries)?;
+        fs::write(path, formatted).await?;
+        info!("Written output to {}", path.display());
+        Ok(())
+    }
+
+    /// File extension matching the output format, without the dot.
+    pub fn extension(&self) -> &str {
+        self.extension
+    }
+
+    /// Opens `path` for incremental output, so summaries that completed
+    /// before a crash or Ctrl-C are not lost.
+    pub async fn open_streaming(&self, path: &Path) -> Result<StreamingOutput<'_>> {
+        let file = fs::File::create(path).await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+
+        Ok(StreamingOutput {
+            writer: self,
+            path: path.to_owned(),
+            file,
+            append: None,
+        })
+    }
+
+    /// Opens `path` to add this run's summaries to what it already holds.
+    /// Appendable formats get the new summaries appended when the run
+    /// finishes. Others (JSON) are read back now, so a file that can't be
+    /// merged fails before any work is done, and rewritten with the merged
+    /// summaries at the end.
+    pub async fn open_appending(&self, path: &Path) -> Result<StreamingOutput<'_>> {
+        let existing = if self.formatter.appendable() {
+            Vec::new()
+        } else {
+            match fs::read_to_string(path).await {
+                Ok(content) if !content.trim().is_empty() => self.formatter.parse(&content)
+                    .with_context(|| format!("Can't append to {}", path.display()))?,
+                Ok(_) => Vec::new(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+            }
+        };
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        Ok(StreamingOutput {
+            writer: self,
+            path: path.to_owned(),
+            file,
+            append: Some(existing),
+        })
+    }
+
+    /// Writes each summary to its own file under `out_dir`, mirroring the
+    /// input path: `docs/api.md` becomes `out_dir/docs/api.md.summary.md`.
+    pub async fn write_split(&self, summaries: &[Summary], out_dir: &Path) -> Result<()> {
+        for summary in summaries {
+            let path = self.split_path(summary, out_dir);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
             }
-            None => println!("{}", formatted),
+
+            let formatted = self.formatter.format(std::slice::from_ref(summary))?;
+            fs::write(&path, formatted).await
+                .with_context(|| format!("Failed to write {}", path.display()))?;
         }
 
+        info!("Written {} summaries to {}", summaries.len(), out_dir.display());
         Ok(())
     }
+
+    /// Writes each summary to the path `template` expands to for it.
+    /// `{stem}` and `{ext}` come from the original file name, `{parent}` is
+    /// the name of its directory, `{date}` is today's date (UTC,
+    /// `YYYY-MM-DD`) and `{model}` is `model`. Summaries that expand to the
+    /// same path share one file.
+    pub async fn write_templated(&self, summaries: Vec<Summary>, template: &str, model: &str) -> Result<()> {
+        let date = humantime::format_rfc3339_seconds(SystemTime::now()).to_string()[..10].to_string();
+        // Model names like `org/model` would otherwise introduce directories
+        let model = model.replace(['/', '\\'], "-");
+
+        let mut files: BTreeMap<PathBuf, Vec<Summary>> = BTreeMap::new();
+        for summary in summaries {
+            let path = expand_template(template, &summary, &date, &model);
+            files.entry(path).or_default().push(summary);
+        }
+
+        for (path, summaries) in &files {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).await
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+
+            let formatted = self.formatter.format(summaries)?;
+            fs::write(path, formatted).await
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        info!("Written output to {} files", files.len());
+        Ok(())
+    }
+
+    /// Writes `out_dir/index.json` listing each summary written by
+    /// [`OutputWriter::write_split`], sorted by original path.
+    pub async fn write_manifest(&self, summaries: &[Summary], out_dir: &Path) -> Result<()> {
+        #[derive(Serialize)]
+        struct ManifestEntry<'a> {
+            original_path: &'a str,
+            /// Relative to the manifest's directory.
+            summary_path: PathBuf,
+            original_size: u64,
+            summary_size: u64,
+            compression_ratio: f64,
+        }
+
+        let mut entries: Vec<ManifestEntry> = summaries
+            .iter()
+            .map(|summary| ManifestEntry {
+                original_path: &summary.original_path,
+                summary_path: self.split_path(summary, Path::new("")),
+                original_size: summary.metadata.original_size,
+                summary_size: summary.metadata.summary_size,
+                compression_ratio: summary.metadata.compression_ratio,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.original_path.cmp(b.original_path));
+
+        let path = out_dir.join("index.json");
+        let json = serde_json::to_string_pretty(&entries).context("Failed to serialize manifest")?;
+        fs::write(&path, json).await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn split_path(&self, summary: &Summary, out_dir: &Path) -> PathBuf {
+        nested_path(Path::new(&summary.original_path), out_dir, &format!(".summary.{}", self.extension))
+    }
+}
+
+/// Mirrors `original` under `out_dir` with `suffix` appended to the file
+/// name. Root, `.` and `..` are dropped so every file lands inside `out_dir`.
+pub fn nested_path(original: &Path, out_dir: &Path, suffix: &str) -> PathBuf {
+    let mut path: PathBuf = original
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    let mut file_name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    file_name.push(suffix);
+    path.set_file_name(file_name);
+    out_dir.join(path)
+}
+
+fn expand_template(template: &str, summary: &Summary, date: &str, model: &str) -> PathBuf {
+    let original = Path::new(&summary.original_path);
+    let name = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().into_owned());
+    let stem = name(original.file_stem()).unwrap_or_default();
+    let ext = name(original.extension()).unwrap_or_default();
+    let parent = name(original.parent().and_then(Path::file_name)).unwrap_or_else(|| ".".to_string());
+
+    PathBuf::from(
+        template
+            .replace("{stem}", &stem)
+            .replace("{ext}", &ext)
+            .replace("{parent}", &parent)
+            .replace("{date}", date)
+            .replace("{model}", model),
+    )
+}
+
+/// A file that receives summaries as they complete. Formats that can't be
+/// appended to (JSON, HTML) are only written by [`StreamingOutput::finish`].
+pub struct StreamingOutput<'a> {
+    writer: &'a OutputWriter,
+    path: PathBuf,
+    file: fs::File,
+    /// Set when adding to an existing file: the summaries read back from
+    /// it, empty for appendable formats.
+    append: Option<Vec<Summary>>,
+}
+
+impl StreamingOutput<'_> {
+    pub async fn write_one(&mut self, summary: &Summary) -> Result<()> {
+        // Appended output is written in order by `finish`
+        if !self.writer.formatter.appendable() || self.append.is_some() {
+            return Ok(());
+        }
+
+        let formatted = self.writer.formatter.format(std::slice::from_ref(summary))?;
+        self.file.write_all(formatted.as_bytes()).await?;
+        self.file.flush().await?;
+        Ok(())
+    }
+
+    /// Replaces the incremental output with the complete, ordered output,
+    /// or adds it to the existing content when appending. Merged summaries
+    /// replace existing ones for the same path.
+    pub async fn finish(mut self, summaries: Vec<Summary>) -> Result<()> {
+        match self.append.take() {
+            None => {
+                drop(self.file);
+                self.writer.write(summaries, Some(&self.path)).await
+            }
+            Some(_) if self.writer.formatter.appendable() => {
+                let formatted = self.writer.formatter.format(&summaries)?;
+                self.file.write_all(formatted.as_bytes()).await?;
+                self.file.flush().await?;
+                info!("Appended {} summaries to {}", summaries.len(), self.path.display());
+                Ok(())
+            }
+            Some(mut existing) => {
+                drop(self.file);
+                existing.retain(|old| !summaries.iter().any(|new| new.original_path == old.original_path));
+                existing.extend(summaries);
+                existing.sort_by(|a, b| a.original_path.cmp(&b.original_path));
+                self.writer.write(existing, Some(&self.path)).await
+            }
+        }
+    }
 }
 
 impl Summary {
-    pub fn new(document: &Document, summary: String) -> Self {
+    /// Replaces the summary text, updating its size and compression ratio.
+    pub fn replace_text(&mut self, text: String) {
+        self.metadata.summary_size = text.len() as u64;
+        self.metadata.compression_ratio = if self.metadata.original_size == 0 {
+            1.0
+        } else {
+            self.metadata.summary_size as f64 / self.metadata.original_size as f64
+        };
+        self.summary = text;
+    }
+
+    pub fn new(document: &Document, generated: GeneratedSummary) -> Self {
+        let summary = generated.text;
         let summary_size = summary.len() as u64;
-        let compression_ratio = summary_size as f64 / document.metadata.file_size as f64;
+        let compression_ratio = if document.metadata.file_size == 0 {
+            1.0
+        } else {
+            summary_size as f64 / document.metadata.file_size as f64
+        };
 
         Self {
             original_path: document.path.to_string_lossy().into_owned(),
+            relative_path: document.relative_path.as_ref().map(|path| path.to_string_lossy().into_owned()),
             summary,
             metadata: SummaryMetadata {
                 original_size: document.metadata.file_size,
                 summary_size,
                 compression_ratio,
+                chunks: generated.chunks,
+                word_count: document.metadata.word_count,
+                language: document.metadata.language.clone(),
+                model: generated.model,
+                input_tokens: generated.usage.input_tokens,
+                output_tokens: generated.usage.output_tokens,
+                max_tokens: generated.max_tokens,
+                title: document.metadata.title(),
+                tags: document.metadata.tags(),
+                content_hash: document.content_hash(),
+                retried: generated.retried,
             },
+            original_content: None,
         }
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,100 @@
+use super::*;
+use crate::processing::{DocumentFormat, DocumentMetadata};
+
+/// A summary of `text` for a 100-byte original at `path`.
+fn summary(path: &str, text: &str) -> Summary {
+    Summary {
+        original_path: path.to_string(),
+        relative_path: None,
+        summary: text.to_string(),
+        metadata: SummaryMetadata {
+            original_size: 100,
+            summary_size: text.len() as u64,
+            compression_ratio: text.len() as f64 / 100.0,
+            ..Default::default()
+        },
+        original_content: None,
+    }
+}
+
+/// A Markdown document at `path` whose original was `file_size` bytes.
+fn document(path: &str, file_size: u64) -> Document {
+    Document {
+        path: path.into(),
+        relative_path: None,
+        content: String::new(),
+        format: DocumentFormat::Markdown,
+        metadata: DocumentMetadata {
+            file_size,
+            encoding: "UTF-8".to_string(),
+            lossy: false,
+            line_count: 0,
+            word_count: 0,
+            char_count: 0,
+            language: None,
+            front_matter: None,
+            modified: None,
+        },
+    }
+}
+
+#[test]
+fn yaml_output_round_trips() {
+    let summaries = vec![summary("docs/api.md", "Covers: the API.\n\n- auth"), summary("docs/intro.md", "Intro")];
+    let output = YamlFormatter { include_metadata: true }.format(&summaries).unwrap();
+
+    let parsed: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+    assert_eq!(parsed[0]["original_path"].as_str(), Some("docs/api.md"));
+    assert_eq!(parsed[0]["summary"].as_str(), Some("Covers: the API.\n\n- auth"));
+    assert_eq!(parsed[0]["metadata"]["compression_ratio"].as_f64(), Some(0.24));
+    assert_eq!(parsed[1]["original_path"].as_str(), Some("docs/intro.md"));
+    assert_eq!(parsed[1]["metadata"]["compression_ratio"].as_f64(), Some(0.05));
+}
+
+#[test]
+fn zero_byte_documents_report_no_compression() {
+    let summary = Summary::new(
+        &document("empty.md", 0),
+        GeneratedSummary {
+            text: "Nothing here.".to_string(),
+            chunks: 1,
+            model: "gpt-4".to_string(),
+            usage: Default::default(),
+            retried: false,
+            max_tokens: 200,
+        },
+    );
+    assert_eq!(summary.metadata.compression_ratio, 1.0);
+
+    let json: serde_json::Value = serde_json::from_str(&JsonFormatter { include_metadata: true }.format(&[summary]).unwrap()).unwrap();
+    assert_eq!(json[0]["metadata"]["compression_ratio"], 1.0);
+}
+
+#[test]
+fn jsonl_writes_one_line_per_summary() {
+    let summaries = vec![
+        summary("a.md", "First.\n\nWith a second paragraph."),
+        summary("b.md", "Second."),
+        summary("c.md", "Third."),
+    ];
+    let output = JsonLinesFormatter { include_metadata: true }.format(&summaries).unwrap();
+
+    let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["summary"], "First.\n\nWith a second paragraph.");
+    assert_eq!(lines[2]["original_path"], "c.md");
+}
+
+#[test]
+fn toml_output_round_trips_as_summary_tables() {
+    let summaries = vec![summary("docs/api.md", "Covers \"v2\".\n\n- auth"), summary("docs/intro.md", "Intro")];
+    let output = TomlFormatter { include_metadata: true }.format(&summaries).unwrap();
+
+    assert_eq!(output.matches("[[summary]]").count(), 2);
+    let parsed: toml::Value = toml::from_str(&output).unwrap();
+    let parsed = parsed["summary"].as_array().unwrap();
+    assert_eq!(parsed[0]["original_path"].as_str(), Some("docs/api.md"));
+    assert_eq!(parsed[0]["summary"].as_str(), Some("Covers \"v2\".\n\n- auth"));
+    assert_eq!(parsed[1]["original_path"].as_str(), Some("docs/intro.md"));
+    assert_eq!(parsed[1]["metadata"]["compression_ratio"].as_float(), Some(0.05));
+}
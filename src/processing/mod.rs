@@ -1,161 +1,1344 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chardetng::EncodingDetector;
 use encoding_rs::Encoding;
 use ignore::WalkBuilder;
-use pulldown_cmark::{Parser, Event};
-use std::path::{Path, PathBuf};
+use futures::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 use tokio::fs;
-use tracing::warn;
-use regex::RegexBuilder;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tracing::{debug, warn};
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+
+use crate::config::{NormalizeConfig, ProcessingConfig};
+use crate::error::DocTldrError;
+use crate::source::SourceLanguage;
+
+/// Per-directory ignore files in `.gitignore` syntax, read while walking
+/// input directories.
+pub const IGNORE_FILENAME: &str = ".doctldrignore";
 
 #[derive(Debug)]
 pub struct Document {
     pub path: PathBuf,
+    /// `path` relative to the input directory it was found under. Unset
+    /// for stdin, URLs and merged directories.
+    pub relative_path: Option<PathBuf>,
     pub content: String,
     pub format: DocumentFormat,
     pub metadata: DocumentMetadata,
 }
 
+impl Document {
+    /// Hex SHA-256 of the preprocessed content, recorded in each summary so
+    /// `--diff` can tell which documents changed since an earlier run.
+    pub fn content_hash(&self) -> String {
+        Sha256::digest(self.content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Combines the documents of one input directory into a single document
+    /// whose path is the directory and whose size is the sum of its parts.
+    pub fn merge(dir: &Path, documents: Vec<Document>) -> Self {
+        let mut content = String::new();
+        let mut file_size = 0;
+        let mut lossy = false;
+        let mut line_count = 0;
+        let mut word_count = 0;
+        let mut char_count = 0;
+        let mut encodings: Vec<String> = Vec::new();
+        let mut languages: Vec<Option<String>> = Vec::new();
+        let mut modified = None;
+
+        for document in documents {
+            content.push_str(&format!("## File: {}\n\n", document.path.display()));
+            content.push_str(&document.content);
+            content.push_str("\n\n");
+
+            file_size += document.metadata.file_size;
+            lossy |= document.metadata.lossy;
+            line_count += document.metadata.line_count;
+            word_count += document.metadata.word_count;
+            char_count += document.metadata.char_count;
+            if !encodings.contains(&document.metadata.encoding) {
+                encodings.push(document.metadata.encoding);
+            }
+            if !languages.contains(&document.metadata.language) {
+                languages.push(document.metadata.language);
+            }
+            modified = modified.max(document.metadata.modified);
+        }
+
+        let encoding = match encodings.as_slice() {
+            [single] => single.clone(),
+            _ => "mixed".to_string(),
+        };
+        let language = match languages.as_slice() {
+            [single] => single.clone(),
+            _ => None,
+        };
+
+        Self {
+            path: dir.to_owned(),
+            relative_path: None,
+            content,
+            format: DocumentFormat::Markdown,
+            metadata: DocumentMetadata {
+                file_size,
+                encoding,
+                lossy,
+                line_count,
+                word_count,
+                char_count,
+                language,
+                // Front matter describes single files
+                front_matter: None,
+                modified,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DocumentMetadata {
     pub file_size: u64,
     pub encoding: String,
+    /// Undecodable bytes were replaced with U+FFFD while decoding.
+    pub lossy: bool,
     pub line_count: usize,
+    pub word_count: usize,
+    pub char_count: usize,
+    /// ISO 639-3 code, when the text is long enough to detect reliably.
+    pub language: Option<String>,
+    /// Leading YAML front matter of markdown files, removed from `content`.
+    pub front_matter: Option<serde_yaml::Value>,
+    /// Modification time of the file; `None` for stdin and URLs.
+    pub modified: Option<SystemTime>,
+}
+
+impl DocumentMetadata {
+    /// The `title` field of the front matter, if it is a string.
+    pub fn title(&self) -> Option<String> {
+        self.front_matter.as_ref()?.get("title")?.as_str().map(str::to_string)
+    }
+
+    /// The `tags` field of the front matter, either a list or a
+    /// comma-separated string.
+    pub fn tags(&self) -> Vec<String> {
+        let tags = match self.front_matter.as_ref().and_then(|fm| fm.get("tags")) {
+            Some(tags) => tags,
+            None => return Vec::new(),
+        };
+
+        match tags {
+            serde_yaml::Value::Sequence(items) => items
+                .iter()
+                .filter_map(|item| match item {
+                    serde_yaml::Value::String(tag) => Some(tag.clone()),
+                    serde_yaml::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            serde_yaml::Value::String(tags) => tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DocumentFormat {
     Markdown,
     RestructuredText,
     Html,
     PlainText,
+    Pdf,
+    AsciiDoc,
+    Docx,
+    Notebook,
+    /// Reduced to doc comments and public signatures before summarizing.
+    SourceCode(SourceLanguage),
 }
 
 impl DocumentFormat {
     fn from_extension(path: &Path) -> Self {
-        match path.extension().and_then(|e| e.to_str()) {
-            Some("md") => Self::Markdown,
-            Some("rst") => Self::RestructuredText,
-            Some("html") | Some("htm") => Self::Html,
-            _ => Self::PlainText,
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_name)
+            .unwrap_or(Self::PlainText)
+    }
+
+    /// Parses a file extension or format name such as `md` or `markdown`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "md" | "markdown" | "mdx" | "mkd" => Some(Self::Markdown),
+            "rst" => Some(Self::RestructuredText),
+            "html" | "htm" => Some(Self::Html),
+            "pdf" => Some(Self::Pdf),
+            "adoc" | "asciidoc" => Some(Self::AsciiDoc),
+            "docx" => Some(Self::Docx),
+            "ipynb" => Some(Self::Notebook),
+            "txt" | "text" => Some(Self::PlainText),
+            name => SourceLanguage::from_name(name).map(Self::SourceCode),
+        }
+    }
+
+    /// Maps a `Content-Type` header value, parameters and all, to a format.
+    /// Generic types such as `text/plain` give `None` so the URL's
+    /// extension can decide.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "text/markdown" | "text/x-markdown" => Some(Self::Markdown),
+            "text/x-rst" | "text/prs.fallenstein.rst" => Some(Self::RestructuredText),
+            "text/asciidoc" | "text/x-asciidoc" => Some(Self::AsciiDoc),
+            "application/pdf" => Some(Self::Pdf),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some(Self::Docx),
+            "application/x-ipynb+json" => Some(Self::Notebook),
+            "text/x-python" | "text/x-script.python" => Some(Self::SourceCode(SourceLanguage::Python)),
+            "text/rust" | "text/x-rust" => Some(Self::SourceCode(SourceLanguage::Rust)),
+            _ => None,
         }
     }
 }
 
+/// A response body fetched by [`DocumentProcessor::fetch_url`], not yet
+/// converted to a [`Document`].
+pub(crate) struct FetchedPage {
+    /// The URL after redirects.
+    pub url: reqwest::Url,
+    pub format: DocumentFormat,
+    pub bytes: Vec<u8>,
+}
+
 pub struct DocumentProcessor {
     max_depth: usize,
+    follow_symlinks: bool,
     include_patterns: Vec<String>,
     exclude_patterns: Vec<String>,
+    /// `processing.format_overrides`, checked in sorted pattern order.
+    format_overrides: Vec<(String, DocumentFormat)>,
+    max_file_size_bytes: u64,
+    read_concurrency: usize,
+    preprocessor: Preprocessor,
+    strict_encoding: bool,
+    fail_fast: bool,
+    modified_since: Option<SystemTime>,
+    changed_paths: Option<HashSet<PathBuf>>,
+    /// Output files and directories of this run, canonicalized.
+    excluded_outputs: Vec<PathBuf>,
+    show_progress: bool,
+    /// Holds the spinners of directories walked concurrently.
+    progress: MultiProgress,
+    skipped: AtomicUsize,
+    failed: AtomicUsize,
 }
 
 impl DocumentProcessor {
-    pub fn new(max_depth: usize, include_patterns: Vec<String>, exclude_patterns: Vec<String>) -> Self {
+    pub fn new(config: &ProcessingConfig, show_progress: bool) -> Self {
         Self {
-            max_depth,
-            include_patterns,
-            exclude_patterns,
+            max_depth: config.max_depth,
+            follow_symlinks: config.follow_symlinks,
+            include_patterns: config.include_patterns.clone(),
+            exclude_patterns: config.exclude_patterns.clone(),
+            // Unknown names are rejected by `Config::validate`
+            format_overrides: config
+                .format_overrides
+                .iter()
+                .filter_map(|(pattern, name)| Some((pattern.clone(), DocumentFormat::from_name(&name.to_lowercase())?)))
+                .collect(),
+            max_file_size_bytes: config.max_file_size_bytes,
+            read_concurrency: config.read_concurrency,
+            preprocessor: Preprocessor {
+                notebook_outputs: config.notebook_outputs,
+                headings_only: config.extract_mode.eq_ignore_ascii_case("headings"),
+                normalize: config.normalize.clone(),
+            },
+            strict_encoding: config.strict_encoding,
+            fail_fast: false,
+            modified_since: None,
+            changed_paths: None,
+            excluded_outputs: Vec::new(),
+            show_progress,
+            progress: MultiProgress::new(),
+            skipped: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
         }
     }
 
+    /// Files skipped for exceeding the size limit so far.
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Files that matched but could not be read or converted so far.
+    pub fn failed_count(&self) -> usize {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Return the first file error from [`DocumentProcessor::process_directory`]
+    /// instead of logging it and skipping the file.
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Only process files modified at or after `time`.
+    pub fn with_modified_since(mut self, time: SystemTime) -> Self {
+        self.modified_since = Some(time);
+        self
+    }
+
+    /// Only process files in `paths`, which must be canonicalized.
+    pub fn with_changed_paths(mut self, paths: HashSet<PathBuf>) -> Self {
+        self.changed_paths = Some(paths);
+        self
+    }
+
+    /// Never process `paths` or anything under them, so output written
+    /// inside an input directory isn't summarized by the next run. The
+    /// paths must be canonicalized; they don't need to exist yet.
+    pub fn with_excluded_outputs(mut self, paths: Vec<PathBuf>) -> Self {
+        self.excluded_outputs = paths;
+        self
+    }
+
     pub async fn process_directory(&self, dir: &Path) -> Result<Vec<Document>> {
-        let mut documents = Vec::new();
+        let spinner = self.spinner(dir)?;
+        let mut paths = Vec::new();
         let walker = WalkBuilder::new(dir)
-            .max_depth(Some(self.max_depth))
+            .max_depth((self.max_depth > 0).then_some(self.max_depth))
+            .follow_links(self.follow_symlinks)
+            // Directory listing order varies between file systems
+            .sort_by_file_name(|a, b| a.cmp(b))
             .standard_filters(true)
+            .add_custom_ignore_filename(IGNORE_FILENAME)
             .build();
 
         for entry in walker {
-            let entry = entry?;
+            let entry = match entry {
+                Ok(entry) => entry,
+                // A cycle or dangling link only loses that entry, not the walk
+                Err(e) if self.follow_symlinks && (is_symlink_loop(&e) || is_not_found(&e)) => {
+                    warn!("Skipping symlink: {}", e);
+                    self.skipped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // The walker does not descend into symlinked directories unless
+            // following, but `is_file` below would still follow file links
+            if !self.follow_symlinks && entry.depth() > 0 && entry.path_is_symlink() {
+                debug!("Skipping symlink {}", entry.path().display());
+                continue;
+            }
             let path = entry.path();
 
-            if !self.should_process_file(path) {
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            if !self.should_process_file(path, relative) {
                 continue;
             }
 
-            match self.process_file(path).await {
+            // Checked before reading so huge files never get loaded into memory
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.len() > self.max_file_size_bytes {
+                    warn!(
+                        "Skipping {}: {} bytes exceeds the {}-byte limit",
+                        path.display(),
+                        metadata.len(),
+                        self.max_file_size_bytes
+                    );
+                    self.skipped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+
+            paths.push((path.to_owned(), self.format_override(relative)));
+        }
+
+        // Files are independent, so up to `read_concurrency` are read and
+        // converted at once. `buffered` keeps the walk order.
+        let mut results = stream::iter(&paths)
+            .map(|(path, format)| async move {
+                let result = self.process_file(path, *format).await.map(|mut document| {
+                    document.relative_path = path.strip_prefix(dir).ok().map(Path::to_path_buf);
+                    document
+                });
+                (path, result)
+            })
+            .buffered(self.read_concurrency.max(1));
+
+        let mut documents = Vec::new();
+        while let Some((path, result)) = results.next().await {
+            match result {
                 Ok(doc) => documents.push(doc),
-                Err(e) => warn!("Failed to process file {}: {}", path.display(), e),
+                Err(e) if self.fail_fast => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    return Err(e.context(format!("Failed to process file {}", path.display())));
+                }
+                Err(e) => {
+                    warn!("Failed to process file {}: {}", path.display(), e);
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                }
             }
+            spinner.set_message(format!("Scanning {}: {} files", dir.display(), documents.len()));
         }
 
+        spinner.finish_and_clear();
         Ok(documents)
     }
 
-    async fn process_file(&self, path: &Path) -> Result<Document> {
-        let content = fs::read(path).await?;
-        let (content, encoding) = detect_and_decode(&content);
+    fn spinner(&self, dir: &Path) -> Result<ProgressBar> {
+        if !self.show_progress {
+            return Ok(ProgressBar::hidden());
+        }
+
+        let spinner = self.progress.add(
+            ProgressBar::new_spinner()
+                .with_style(ProgressStyle::with_template("{spinner} {msg}")?)
+                .with_message(format!("Scanning {}", dir.display())),
+        );
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        Ok(spinner)
+    }
 
-        let metadata = DocumentMetadata {
-            file_size: content.len() as u64,
-            encoding: encoding.name().to_string(),
-            line_count: content.lines().count(),
+    /// `format` overrides the format the extension implies.
+    async fn process_file(&self, path: &Path, format: Option<DocumentFormat>) -> Result<Document> {
+        let bytes = fs::read(path).await?;
+        let mut document = match gzip_inner_path(path) {
+            Some(inner) => {
+                let bytes = gunzip(bytes).await?;
+                let format = format.unwrap_or_else(|| DocumentFormat::from_extension(&inner));
+                self.process_bytes(path, bytes, format).await?
+            }
+            None => {
+                let format = format.unwrap_or_else(|| DocumentFormat::from_extension(path));
+                self.process_bytes(path, bytes, format).await?
+            }
         };
+        document.metadata.modified = fs::metadata(path).await.ok().and_then(|m| m.modified().ok());
+        Ok(document)
+    }
 
-        let format = DocumentFormat::from_extension(path);
-        let content = self.preprocess_content(&content, &format)?;
+    /// Reads a single document from stdin. Its path is reported as `<stdin>`.
+    pub async fn process_stdin(&self, format: DocumentFormat) -> Result<Document> {
+        let mut bytes = Vec::new();
+        tokio::io::stdin().read_to_end(&mut bytes).await?;
+        self.process_bytes(Path::new("<stdin>"), bytes, format).await
+    }
 
-        Ok(Document {
-            path: path.to_owned(),
-            content,
+    /// Fetches a document over HTTP(S). The format comes from the
+    /// `Content-Type` header, then the URL's extension, then plain text.
+    /// Redirects are followed by `client`; any final status other than
+    /// success is an error.
+    pub async fn process_url(&self, url: &str, client: &reqwest::Client) -> Result<Document> {
+        let page = self.fetch_url(url, client).await?;
+        self.process_bytes(Path::new(url), page.bytes, page.format).await
+    }
+
+    pub(crate) async fn fetch_url(&self, url: &str, client: &reqwest::Client) -> Result<FetchedPage> {
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch {}", url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Failed to fetch {}: HTTP {}", url, status);
+        }
+        if response.url().as_str() != url {
+            debug!("{} redirected to {}", url, response.url());
+        }
+        if let Some(length) = response.content_length().filter(|&length| length > self.max_file_size_bytes) {
+            anyhow::bail!("{} is {} bytes, over the {}-byte limit", url, length, self.max_file_size_bytes);
+        }
+
+        let final_url = response.url().clone();
+        let format = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(DocumentFormat::from_content_type)
+            .unwrap_or_else(|| DocumentFormat::from_extension(Path::new(final_url.path())));
+        let bytes = response.bytes().await.with_context(|| format!("Failed to read {}", url))?;
+        if bytes.len() as u64 > self.max_file_size_bytes {
+            anyhow::bail!("{} is {} bytes, over the {}-byte limit", url, bytes.len(), self.max_file_size_bytes);
+        }
+
+        Ok(FetchedPage {
+            url: final_url,
             format,
-            metadata,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub(crate) async fn process_bytes(&self, path: &Path, bytes: Vec<u8>, format: DocumentFormat) -> Result<Document> {
+        // Binary formats are extracted to text before any encoding detection
+        let (content, encoding, lossy) = match format {
+            DocumentFormat::Pdf => (extract_pdf_text(bytes).await.map_err(DocTldrError::Decode)?, "PDF".to_string(), false),
+            DocumentFormat::Docx => (extract_docx_text(bytes).await.map_err(DocTldrError::Decode)?, "DOCX".to_string(), false),
+            _ => {
+                let (content, encoding, lossy) = detect_and_decode(&bytes);
+                (content, encoding.name().to_string(), lossy)
+            }
+        };
+
+        if lossy {
+            if self.strict_encoding {
+                return Err(DocTldrError::Decode(anyhow::anyhow!("not valid {} text (--strict-encoding)", encoding)).into());
+            }
+            warn!("{} is not valid {} text; undecodable bytes were replaced", path.display(), encoding);
+        }
+
+        // Language detection and markup stripping are CPU-bound; the blocking
+        // pool lets concurrently read files be converted in parallel
+        let preprocessor = self.preprocessor.clone();
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let (front_matter, body) = match format {
+                DocumentFormat::Markdown => split_front_matter(&content),
+                _ => (None, content.as_str()),
+            };
+
+            let metadata = DocumentMetadata {
+                file_size: content.len() as u64,
+                encoding,
+                lossy,
+                line_count: content.lines().count(),
+                word_count: body.split_whitespace().count(),
+                char_count: content.chars().count(),
+                language: detect_language(body),
+                front_matter,
+                modified: None,
+            };
+
+            let content = preprocessor.preprocess(body, &format)?;
+
+            Ok(Document {
+                path,
+                relative_path: None,
+                content,
+                format,
+                metadata,
+            })
         })
+        .await
+        .map_err(|e| anyhow::anyhow!("Document processing panicked: {}", e))?
     }
 
-    fn should_process_file(&self, path: &Path) -> bool {
+    /// The format of the first `format_overrides` pattern matching
+    /// `relative`, or of its name without `.gz`.
+    fn format_override(&self, relative: &Path) -> Option<DocumentFormat> {
+        let inner = gzip_inner_path(relative);
+        self.format_overrides
+            .iter()
+            .find(|(pattern, _)| {
+                glob_matches(pattern, relative) || inner.as_deref().is_some_and(|inner| glob_matches(pattern, inner))
+            })
+            .map(|(_, format)| *format)
+    }
+
+    /// `relative` is `path` relative to the input directory; glob patterns
+    /// are matched against it.
+    fn should_process_file(&self, path: &Path, relative: &Path) -> bool {
         if !path.is_file() {
             return false;
         }
 
-        let path_str = path.to_string_lossy();
-        
+        if let Some(since) = self.modified_since {
+            let modified = path.metadata().and_then(|m| m.modified());
+            if !matches!(modified, Ok(modified) if modified >= since) {
+                return false;
+            }
+        }
+
+        if !self.excluded_outputs.is_empty() {
+            if let Ok(canonical) = path.canonicalize() {
+                if self.excluded_outputs.iter().any(|output| canonical.starts_with(output)) {
+                    debug!("Skipping {}: output of this run", path.display());
+                    return false;
+                }
+            }
+        }
+
+        if let Some(changed) = &self.changed_paths {
+            match path.canonicalize() {
+                Ok(path) if changed.contains(&path) => {}
+                _ => return false,
+            }
+        }
+
+        // `docs/api.md.gz` is matched both as itself and as `docs/api.md`,
+        // so `*.md` covers compressed Markdown too
+        let inner = gzip_inner_path(relative);
+        let matches = |pattern: &String| {
+            glob_matches(pattern, relative) || inner.as_deref().is_some_and(|inner| glob_matches(pattern, inner))
+        };
+
         // Check exclude patterns first
-        if self.exclude_patterns.iter().any(|pattern| {
-            let regex = RegexBuilder::new(&glob_to_regex(pattern))
-                .case_insensitive(true)
-                .build()
-                .unwrap_or_else(|_| RegexBuilder::new(".^").build().unwrap());
-            regex.is_match(&path_str)
-        }) {
+        if self.exclude_patterns.iter().any(matches) {
             return false;
         }
 
         // Then check include patterns
-        self.include_patterns.iter().any(|pattern| {
-            let regex = RegexBuilder::new(&glob_to_regex(pattern))
-                .case_insensitive(true)
-                .build()
-                .unwrap_or_else(|_| RegexBuilder::new(".^").build().unwrap());
-            regex.is_match(&path_str)
-        })
+        self.include_patterns.iter().any(matches)
     }
+}
 
-    fn preprocess_content(&self, content: &str, format: &DocumentFormat) -> Result<String> {
+/// Format-specific cleanup of decoded text. Only holds plain settings, so
+/// it can be moved onto a blocking thread.
+#[derive(Debug, Clone)]
+struct Preprocessor {
+    notebook_outputs: bool,
+    /// `extract_mode = "headings"`: keep only the outline of Markdown and
+    /// HTML.
+    headings_only: bool,
+    normalize: NormalizeConfig,
+}
+
+impl Preprocessor {
+    fn preprocess(&self, content: &str, format: &DocumentFormat) -> Result<String> {
+        let content = self.extract(content, format)?;
+        normalize(content, &self.normalize)
+    }
+
+    fn extract(&self, content: &str, format: &DocumentFormat) -> Result<String> {
         match format {
             DocumentFormat::Markdown => self.process_markdown(content),
+            // html2text renders headings Markdown-style, so its output
+            // reduces like Markdown
+            DocumentFormat::Html if self.headings_only => {
+                Ok(markdown_skeleton(&html2text::from_read(content.as_bytes(), 80)))
+            }
             DocumentFormat::Html => Ok(html2text::from_read(content.as_bytes(), 80)),
-            DocumentFormat::RestructuredText => Ok(content.to_string()), // TODO: Implement RST processing
+            DocumentFormat::RestructuredText => self.process_restructured_text(content),
             DocumentFormat::PlainText => Ok(content.to_string()),
+            DocumentFormat::Pdf => Ok(content.to_string()),
+            DocumentFormat::Docx => Ok(content.to_string()),
+            DocumentFormat::AsciiDoc => self.process_asciidoc(content),
+            DocumentFormat::Notebook => self.process_notebook(content),
+            DocumentFormat::SourceCode(language) => language.extractor().extract(content),
         }
     }
 
+    /// Re-emits markdown without inline markup, links or HTML, keeping the
+    /// outline: headings, list bullets, block quotes, rules and code fences.
+    /// JSX tags in MDX parse as HTML and are dropped with it.
     fn process_markdown(&self, content: &str) -> Result<String> {
+        let content = strip_mdx_statements(content)?;
+        if self.headings_only {
+            return Ok(markdown_skeleton(&content));
+        }
         let mut output = String::new();
-        let parser = Parser::new(content);
-        
+        // One entry per open list: the next ordinal, or None for bullets
+        let mut lists: Vec<Option<u64>> = Vec::new();
+        // Output offsets where each open block quote began
+        let mut quotes: Vec<usize> = Vec::new();
+        let parser = Parser::new(&content);
+
         for event in parser {
             match event {
+                Event::Start(Tag::Heading(level, _, _)) => {
+                    start_block(&mut output);
+                    output.push_str(&"#".repeat(level as usize));
+                    output.push(' ');
+                }
+                Event::End(Tag::Heading(..)) => output.push_str("\n\n"),
+                Event::End(Tag::Paragraph) => {
+                    output.push_str(if lists.is_empty() { "\n\n" } else { "\n" });
+                }
+                Event::Start(Tag::List(start)) => {
+                    end_line(&mut output);
+                    lists.push(start);
+                }
+                Event::End(Tag::List(_)) => {
+                    lists.pop();
+                    if lists.is_empty() {
+                        end_line(&mut output);
+                        output.push('\n');
+                    }
+                }
+                Event::Start(Tag::Item) => {
+                    end_line(&mut output);
+                    output.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                    match lists.last_mut() {
+                        Some(Some(ordinal)) => {
+                            output.push_str(&format!("{}. ", ordinal));
+                            *ordinal += 1;
+                        }
+                        _ => output.push_str("- "),
+                    }
+                }
+                Event::End(Tag::Item) => end_line(&mut output),
+                Event::Start(Tag::BlockQuote) => {
+                    start_block(&mut output);
+                    quotes.push(output.len());
+                }
+                Event::End(Tag::BlockQuote) => {
+                    if let Some(start) = quotes.pop() {
+                        let quoted = output.split_off(start);
+                        for line in quoted.trim_end().lines() {
+                            output.push_str(format!("> {}", line).trim_end());
+                            output.push('\n');
+                        }
+                        output.push('\n');
+                    }
+                }
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    end_line(&mut output);
+                    output.push_str("```");
+                    // Keep only the language from info strings like `rust,no_run`
+                    if let CodeBlockKind::Fenced(info) = kind {
+                        output.push_str(info.split([',', ' ']).next().unwrap_or(""));
+                    }
+                    output.push('\n');
+                }
+                Event::End(Tag::CodeBlock(_)) => {
+                    end_line(&mut output);
+                    output.push_str("```\n\n");
+                }
+                Event::Rule => {
+                    start_block(&mut output);
+                    output.push_str("---\n\n");
+                }
+                Event::TaskListMarker(done) => output.push_str(if done { "[x] " } else { "[ ] " }),
                 Event::Text(text) => output.push_str(&text),
-                Event::Code(code) => output.push_str(&code),
+                Event::Code(code) => output.push_str(&format!("`{}`", code)),
                 Event::SoftBreak | Event::HardBreak => output.push('\n'),
                 _ => {}
             }
         }
 
+        Ok(output.trim_end().to_string())
+    }
+
+    /// Heuristic RST cleanup: drops section adornments, comments and link
+    /// targets, unwraps directive bodies (keeping code blocks verbatim) and
+    /// flattens inline roles to their text.
+    fn process_restructured_text(&self, content: &str) -> Result<String> {
+        let directive = Regex::new(r"^\.\.\s+([A-Za-z0-9][\w.+:-]*?)::(?:\s+(.*))?$")?;
+        let role = Regex::new(r":[\w.+:-]+:`([^`]*)`")?;
+        let link = Regex::new(r"`([^`<]*?)\s*(?:<[^>]*>)?`__?")?;
+        let literal = Regex::new(r"``([^`]+)``")?;
+
+        let lines: Vec<&str> = content.lines().collect();
+        let text = strip_rst_blocks(&lines, &directive).join("\n");
+
+        let text = role.replace_all(&text, |caps: &regex::Captures| {
+            let inner = caps[1].trim_start_matches(['~', '!']);
+            match inner.find('<') {
+                Some(idx) if idx > 0 && inner.ends_with('>') => inner[..idx].trim_end().to_string(),
+                _ => inner.to_string(),
+            }
+        });
+        let text = link.replace_all(&text, "$1");
+        let text = literal.replace_all(&text, "`$1`");
+
+        Ok(text.into_owned())
+    }
+
+    /// Converts a Jupyter notebook to markdown: markdown cells as-is, code
+    /// cells in fences tagged with the kernel language, and (if enabled)
+    /// text outputs in plain fences. Image and other binary outputs are
+    /// dropped.
+    fn process_notebook(&self, content: &str) -> Result<String> {
+        let notebook: serde_json::Value = serde_json::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse notebook JSON: {}", e))?;
+        let language = notebook["metadata"]["kernelspec"]["language"].as_str()
+            .or_else(|| notebook["metadata"]["language_info"]["name"].as_str())
+            .unwrap_or("");
+
+        let mut output = String::new();
+        for cell in notebook["cells"].as_array().into_iter().flatten() {
+            let source = notebook_text(&cell["source"]);
+            match cell["cell_type"].as_str() {
+                Some("markdown") => {
+                    output.push_str(source.trim_end());
+                    output.push_str("\n\n");
+                }
+                Some("code") if !source.trim().is_empty() => {
+                    output.push_str(&format!("```{}\n{}\n```\n\n", language, source.trim_end()));
+                    if !self.notebook_outputs {
+                        continue;
+                    }
+                    for cell_output in cell["outputs"].as_array().into_iter().flatten() {
+                        // The text/plain of an image is just a repr like `<Figure>`
+                        let is_image = cell_output["data"].as_object()
+                            .is_some_and(|data| data.keys().any(|mime| mime.starts_with("image/")));
+                        let text = match cell_output["output_type"].as_str() {
+                            Some("stream") => notebook_text(&cell_output["text"]),
+                            _ if is_image => continue,
+                            _ => notebook_text(&cell_output["data"]["text/plain"]),
+                        };
+                        if !text.trim().is_empty() {
+                            output.push_str(&format!("```\n{}\n```\n\n", text.trim_end()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(output.trim_end().to_string())
+    }
+
+    /// Heuristic AsciiDoc cleanup: drops block delimiters, attribute entries,
+    /// block attribute lines and comments, turns `=` titles into markdown
+    /// headings, and keeps listing block contents verbatim in code fences.
+    fn process_asciidoc(&self, content: &str) -> Result<String> {
+        let title = Regex::new(r"^(=+)\s+(.+)$")?;
+        let attribute = Regex::new(r"^:!?[\w-]+!?:(\s.*)?$")?;
+        let block_attribute = Regex::new(r"^\[\[?[^\]]*\]\]?$")?;
+        let directive = Regex::new(r"^(include|ifdef|ifndef|ifeval|endif)::")?;
+        let source = Regex::new(r"^\[source,\s*([\w+#-]+)")?;
+
+        let mut output = Vec::new();
+        // Closing delimiter of the verbatim or comment block we're inside
+        let mut open_block: Option<String> = None;
+        // Language from a preceding `[source,lang]` line
+        let mut language = String::new();
+
+        for line in content.lines() {
+            let trimmed = line.trim_end();
+
+            if let Some(delimiter) = &open_block {
+                if trimmed == delimiter {
+                    if delimiter.starts_with('-') {
+                        output.push("```".to_string());
+                    }
+                    open_block = None;
+                } else if !delimiter.starts_with('/') {
+                    output.push(line.to_string());
+                }
+                continue;
+            }
+
+            if is_asciidoc_delimiter(trimmed) {
+                match trimmed.chars().next() {
+                    Some('-') if trimmed.len() >= 4 => {
+                        output.push(format!("```{}", language));
+                        open_block = Some(trimmed.to_string());
+                    }
+                    Some('.') | Some('+') | Some('/') => open_block = Some(trimmed.to_string()),
+                    _ => {}
+                }
+                language.clear();
+                continue;
+            }
+
+            if let Some(caps) = source.captures(trimmed) {
+                language = caps[1].to_string();
+                continue;
+            }
+
+            if trimmed.starts_with("//")
+                || attribute.is_match(trimmed)
+                || block_attribute.is_match(trimmed)
+                || directive.is_match(trimmed)
+            {
+                continue;
+            }
+
+            if let Some(caps) = title.captures(trimmed) {
+                output.push(format!("{} {}", "#".repeat(caps[1].len()), &caps[2]));
+            } else if let Some(block_title) = trimmed.strip_prefix('.').filter(|t| t.starts_with(char::is_alphanumeric)) {
+                output.push(block_title.to_string());
+            } else {
+                output.push(line.to_string());
+            }
+        }
+
+        Ok(output.join("\n"))
+    }
+}
+
+/// Block delimiters: four or more of one of `= - . * _ + /`, the `--` open
+/// block, or a `|===` table boundary.
+fn is_asciidoc_delimiter(line: &str) -> bool {
+    if line == "--" || line.starts_with("|===") {
+        return true;
+    }
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(first) if "=-.*_+/".contains(first) && line.len() >= 4 => chars.all(|c| c == first),
+        _ => false,
+    }
+}
+
+/// Walks RST lines, handling explicit markup blocks (`..`) by indentation.
+fn strip_rst_blocks(lines: &[&str], directive: &Regex) -> Vec<String> {
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+
+        if trimmed == ".." || trimmed.starts_with(".. ") {
+            let indent = indentation(line);
+            let mut end = i + 1;
+            while end < lines.len() && (lines[end].trim().is_empty() || indentation(lines[end]) > indent) {
+                end += 1;
+            }
+
+            // Anything that isn't a directive (comments, `.. _targets:`,
+            // substitutions, footnotes) is dropped along with its body.
+            if let Some(caps) = directive.captures(trimmed) {
+                let name = caps[1].to_lowercase();
+                let argument = caps.get(2).map_or("", |m| m.as_str().trim());
+                let body: Vec<&str> = lines[i + 1..end]
+                    .iter()
+                    .copied()
+                    .skip_while(|l| is_rst_option(l))
+                    .collect();
+                let body = dedent(&body);
+
+                match name.as_str() {
+                    "code-block" | "code" | "sourcecode" | "literalinclude" => output.extend(body),
+                    "toctree" | "image" | "include" | "raw" | "meta" | "contents" => {}
+                    _ => {
+                        if !argument.is_empty() {
+                            output.push(argument.to_string());
+                        }
+                        let body: Vec<&str> = body.iter().map(String::as_str).collect();
+                        output.extend(strip_rst_blocks(&body, directive));
+                    }
+                }
+            }
+
+            i = end;
+            continue;
+        }
+
+        if is_rst_adornment(trimmed) || trimmed == "::" {
+            i += 1;
+            continue;
+        }
+
+        // A trailing `::` introduces a literal block; it renders as a single colon.
+        match line.trim_end().strip_suffix("::") {
+            Some(prefix) => output.push(format!("{}:", prefix.trim_end())),
+            None => output.push(line.to_string()),
+        }
+        i += 1;
+    }
+
+    output
+}
+
+/// Section over/underlines: a run of one repeated punctuation character.
+fn is_rst_adornment(line: &str) -> bool {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_punctuation() && line.len() >= 3 => chars.all(|c| c == first),
+        _ => false,
+    }
+}
+
+fn is_rst_option(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with(':') && trimmed[1..].contains(':')
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn dedent(lines: &[&str]) -> Vec<String> {
+    let min_indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| indentation(l))
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|l| l.get(min_indent..).unwrap_or("").to_string())
+        .collect()
+}
+
+/// Splits leading `---` delimited YAML front matter from the body. The
+/// closing delimiter may also be `...`. Anything that doesn't parse as a
+/// YAML mapping, such as a document opening with a thematic break, is left
+/// in the body.
+fn split_front_matter(content: &str) -> (Option<serde_yaml::Value>, &str) {
+    let rest = match content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (None, content),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let delimiter = line.trim_end();
+        if delimiter == "---" || delimiter == "..." {
+            let body = &rest[offset + line.len()..];
+            return match serde_yaml::from_str::<serde_yaml::Value>(&rest[..offset]) {
+                Ok(value @ serde_yaml::Value::Mapping(_)) => (Some(value), body),
+                _ => (None, content),
+            };
+        }
+        offset += line.len();
+    }
+
+    (None, content)
+}
+
+/// Removes MDX `import`/`export` statements outside code fences. Exports
+/// whose braces span several lines are skipped until the braces balance.
+fn strip_mdx_statements(content: &str) -> Result<String> {
+    let statement = Regex::new(
+        r#"^(import\s+(.+\s+from\s+)?['"][^'"]+['"];?\s*$|export\s+(default|const|let|var|function|\{))"#,
+    )?;
+    let mut output = String::with_capacity(content.len());
+    let mut in_fence = false;
+    let mut open_braces = 0usize;
+
+    for line in content.lines() {
+        if open_braces > 0 {
+            open_braces = (open_braces + line.matches('{').count()).saturating_sub(line.matches('}').count());
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && statement.is_match(line) {
+            open_braces = line.matches('{').count().saturating_sub(line.matches('}').count());
+            continue;
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Notebook text fields are either a string or an array of lines.
+fn notebook_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(lines) => lines.iter().filter_map(|line| line.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Ends the current line, if any.
+fn end_line(output: &mut String) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+fn is_symlink_loop(error: &ignore::Error) -> bool {
+    match error {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => is_symlink_loop(err),
+        _ => false,
+    }
+}
+
+fn is_not_found(error: &ignore::Error) -> bool {
+    error.io_error().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Applies `[processing.normalize]`: URLs go first, so the spaces they
+/// leave behind are trimmed, and blank lines are collapsed last.
+fn normalize(content: String, options: &NormalizeConfig) -> Result<String> {
+    let mut content = content;
+    if options.strip_urls {
+        // Trailing sentence punctuation is not part of the URL
+        let url = Regex::new(r#"\b(?:https?|ftp)://[^\s<>"'`)\]]*[^\s<>"'`)\].,;:!?]"#)?;
+        let placeholder = options.url_placeholder.as_deref().unwrap_or("");
+        content = url.replace_all(&content, regex::NoExpand(placeholder)).into_owned();
+    }
+    if !options.trim_trailing_whitespace && !options.collapse_blank_lines {
+        return Ok(content);
+    }
+
+    let mut output = String::with_capacity(content.len());
+    let mut previous_blank = false;
+    for line in content.lines() {
+        let blank = line.trim().is_empty();
+        if options.collapse_blank_lines && blank && previous_blank {
+            continue;
+        }
+        previous_blank = blank;
+        output.push_str(if options.trim_trailing_whitespace { line.trim_end() } else { line });
+        output.push('\n');
+    }
+    if !content.ends_with('\n') {
+        output.pop();
+    }
+    Ok(output)
+}
+
+/// Reduces Markdown to its outline: every heading, each followed by the
+/// first sentence of the first paragraph under it. Code blocks, tight lists
+/// and the rest of each section are dropped. Text before the first heading
+/// is reduced like a section.
+fn markdown_skeleton(content: &str) -> String {
+    let mut output = String::new();
+    let mut heading: Option<String> = None;
+    let mut paragraph: Option<String> = None;
+    // Whether the current section already has its sentence
+    let mut section_done = false;
+    let mut in_code = false;
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) => heading = Some(format!("{} ", "#".repeat(level as usize))),
+            Event::End(Tag::Heading(..)) => {
+                if let Some(heading) = heading.take() {
+                    start_block(&mut output);
+                    output.push_str(heading.trim_end());
+                    output.push_str("\n\n");
+                }
+                section_done = false;
+            }
+            Event::Start(Tag::Paragraph) if !section_done => paragraph = Some(String::new()),
+            Event::End(Tag::Paragraph) => {
+                if let Some(text) = paragraph.take() {
+                    let sentence = first_sentence(&text);
+                    if !sentence.is_empty() {
+                        start_block(&mut output);
+                        output.push_str(sentence);
+                        output.push_str("\n\n");
+                        section_done = true;
+                    }
+                }
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(Tag::CodeBlock(_)) => in_code = false,
+            Event::Text(text) if !in_code => {
+                if let Some(open) = heading.as_mut().or(paragraph.as_mut()) {
+                    open.push_str(&text);
+                }
+            }
+            Event::Code(code) => {
+                if let Some(open) = heading.as_mut().or(paragraph.as_mut()) {
+                    open.push_str(&format!("`{}`", code));
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(open) = heading.as_mut().or(paragraph.as_mut()) {
+                    open.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    output.trim_end().to_string()
+}
+
+/// `text` up to and including the first `.`, `!` or `?` that is followed
+/// by whitespace or ends it, trimmed.
+fn first_sentence(text: &str) -> &str {
+    let text = text.trim();
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') && chars.peek().is_none_or(|(_, next)| next.is_whitespace()) {
+            return &text[..index + c.len_utf8()];
+        }
+    }
+    text
+}
+
+/// Separates a new block from preceding text with a blank line.
+fn start_block(output: &mut String) {
+    end_line(output);
+    if !output.is_empty() && !output.ends_with("\n\n") {
+        output.push('\n');
+    }
+}
+
+/// Shorter texts are skipped; detection on them is mostly noise.
+const MIN_LANGUAGE_DETECTION_WORDS: usize = 20;
+
+/// Detection only looks at this many leading characters to stay cheap on
+/// large documents.
+const LANGUAGE_DETECTION_SAMPLE_CHARS: usize = 2000;
+
+fn detect_language(content: &str) -> Option<String> {
+    content.split_whitespace().nth(MIN_LANGUAGE_DETECTION_WORDS - 1)?;
+
+    let sample = match content.char_indices().nth(LANGUAGE_DETECTION_SAMPLE_CHARS) {
+        Some((end, _)) => &content[..end],
+        None => content,
+    };
+
+    whatlang::detect(sample)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string())
+}
+
+/// Parses a `--since` value: an RFC 3339 timestamp, a bare `YYYY-MM-DD`
+/// date, or a duration relative to now such as `7d` or `12h`.
+pub fn parse_since(value: &str) -> Result<SystemTime> {
+    let value = value.trim();
+    if let Ok(time) = humantime::parse_rfc3339_weak(value) {
+        return Ok(time);
+    }
+    if let Ok(time) = humantime::parse_rfc3339_weak(&format!("{}T00:00:00", value)) {
+        return Ok(time);
+    }
+
+    let duration = humantime::parse_duration(value).map_err(|_| {
+        anyhow::anyhow!("Invalid --since value '{}': expected an RFC 3339 date or a duration like 7d", value)
+    })?;
+    SystemTime::now()
+        .checked_sub(duration)
+        .ok_or_else(|| anyhow::anyhow!("--since duration '{}' is too large", value))
+}
+
+/// Collects the files that differ from `git_ref` in the repositories
+/// containing `dirs`, as canonical paths.
+pub async fn git_changed_paths(dirs: &[PathBuf], git_ref: &str) -> Result<HashSet<PathBuf>> {
+    let mut changed = HashSet::new();
+
+    for dir in dirs {
+        let toplevel = run_git(dir, &["rev-parse", "--show-toplevel"]).await?;
+        let toplevel = PathBuf::from(toplevel.trim());
+        let names = run_git(dir, &["diff", "--name-only", git_ref, "--"]).await?;
+
+        for name in names.lines().filter(|line| !line.is_empty()) {
+            // Deleted files can't be canonicalized and can't be processed anyway
+            if let Ok(path) = toplevel.join(name).canonicalize() {
+                changed.insert(path);
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Shallow-clones the git repository at `url` into a temporary directory,
+/// removed when the returned handle is dropped. `rev` is a branch, tag or
+/// commit to check out instead of the remote's default branch.
+pub async fn clone_git_repo(url: &str, rev: Option<&str>) -> Result<tempfile::TempDir> {
+    let checkout = tempfile::Builder::new()
+        .prefix("doctldr-")
+        .tempdir()
+        .context("Failed to create a directory for the checkout")?;
+    let dir = checkout.path();
+
+    // `git clone --depth 1` can only check out branches and tags; fetching
+    // a single revision into an empty repository handles commits too
+    run_git(dir, &["init", "--quiet"]).await?;
+    run_git(dir, &["fetch", "--quiet", "--depth", "1", url, rev.unwrap_or("HEAD")]).await
+        .with_context(|| format!("Failed to clone {}", url))?;
+    run_git(dir, &["checkout", "--quiet", "FETCH_HEAD"]).await?;
+    debug!("Checked out {} into {}", url, dir.display());
+
+    Ok(checkout)
+}
+
+async fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Extracts text from PDF bytes on a blocking thread. `pdf-extract` can panic
+/// on malformed or encrypted input, which surfaces here as an error instead.
+async fn extract_pdf_text(bytes: Vec<u8>) -> Result<String> {
+    tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&bytes))
+        .await
+        .map_err(|e| anyhow::anyhow!("PDF extraction panicked: {}", e))?
+        .map_err(|e| anyhow::anyhow!("Failed to extract PDF text: {}", e))
+}
+
+/// For a gzip-compressed file like `api.md.gz`, the path without `.gz`.
+fn gzip_inner_path(path: &Path) -> Option<PathBuf> {
+    let is_gzip = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    is_gzip.then(|| path.with_extension(""))
+}
+
+async fn gunzip(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let mut output = Vec::new();
+        flate2::read::GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut output)
+            .map_err(|e| anyhow::anyhow!("Failed to decompress gzip: {}", e))?;
         Ok(output)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("gzip decompression panicked: {}", e))?
+}
+
+const WORDPROCESSINGML_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+async fn extract_docx_text(bytes: Vec<u8>) -> Result<String> {
+    tokio::task::spawn_blocking(move || docx_text(&bytes))
+        .await
+        .map_err(|e| anyhow::anyhow!("DOCX extraction panicked: {}", e))?
+}
+
+/// Reads `word/document.xml` from the archive and emits one line per
+/// paragraph, keeping tabs and explicit line breaks.
+fn docx_text(bytes: &[u8]) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))
+        .map_err(|e| anyhow::anyhow!("Not a valid DOCX archive: {}", e))?;
+    let mut xml = String::new();
+    archive.by_name("word/document.xml")
+        .map_err(|e| anyhow::anyhow!("DOCX has no word/document.xml: {}", e))?
+        .read_to_string(&mut xml)?;
+    let document = roxmltree::Document::parse(&xml)
+        .map_err(|e| anyhow::anyhow!("Failed to parse DOCX XML: {}", e))?;
+
+    let is_word = |node: &roxmltree::Node, name: &str| {
+        node.tag_name().name() == name && node.tag_name().namespace() == Some(WORDPROCESSINGML_NS)
+    };
+
+    let mut output = String::new();
+    for paragraph in document.descendants().filter(|node| is_word(node, "p")) {
+        for node in paragraph.descendants() {
+            if is_word(&node, "t") {
+                output.push_str(node.text().unwrap_or(""));
+            } else if is_word(&node, "tab") {
+                output.push('\t');
+            } else if is_word(&node, "br") || is_word(&node, "cr") {
+                output.push('\n');
+            }
+        }
+        output.push('\n');
     }
+
+    Ok(output)
 }
 
-fn detect_and_decode(content: &[u8]) -> (String, &'static Encoding) {
+/// Decodes `content`, returning the text, the encoding used, and whether
+/// undecodable bytes had to be replaced. Only BOM-marked input and the final
+/// UTF-8 fallback can be lossy; the legacy encodings accept any byte.
+fn detect_and_decode(content: &[u8]) -> (String, &'static Encoding, bool) {
     // Try to detect BOM first
     if content.starts_with(&[0xEF, 0xBB, 0xBF]) {
         return decode_with_encoding(content, encoding_rs::UTF_8);
@@ -165,12 +1348,24 @@ fn detect_and_decode(content: &[u8]) -> (String, &'static Encoding) {
     }
     
     // Try UTF-8 first
-    let (text, encoding, had_errors) = encoding_rs::UTF_8.decode(content);
+    let (text, _, had_errors) = encoding_rs::UTF_8.decode(content);
     if !had_errors {
-        return (text.into_owned(), encoding_rs::UTF_8);
+        return (text.into_owned(), encoding_rs::UTF_8, false);
     }
     
-    // If UTF-8 fails, try other common encodings
+    // Guess statistically; this is what recognizes CJK encodings like
+    // Shift_JIS, GBK and EUC-KR
+    let mut detector = EncodingDetector::new();
+    detector.feed(content, true);
+    let (guess, confident) = detector.guess_assess(None, false);
+    if confident {
+        let (text, enc, had_errors) = guess.decode(content);
+        if !had_errors {
+            return (text.into_owned(), enc, false);
+        }
+    }
+
+    // Low-confidence guesses fall back to common legacy encodings
     for encoding in &[
         encoding_rs::WINDOWS_1252,
         encoding_rs::MACINTOSH,
@@ -178,30 +1373,64 @@ fn detect_and_decode(content: &[u8]) -> (String, &'static Encoding) {
     ] {
         let (text, enc, had_errors) = encoding.decode(content);
         if !had_errors {
-            return (text.into_owned(), enc);
+            return (text.into_owned(), enc, false);
         }
     }
     
     // Fallback to lossy UTF-8
-    match String::from_utf8_lossy(content) {
-        std::borrow::Cow::Borrowed(text) => (text.to_string(), encoding_rs::UTF_8),
-        std::borrow::Cow::Owned(text) => (text, encoding_rs::UTF_8),
-    }
+    (String::from_utf8_lossy(content).into_owned(), encoding_rs::UTF_8, true)
 }
 
-fn decode_with_encoding(content: &[u8], encoding: &'static Encoding) -> (String, &'static Encoding) {
-    let (text, _, _) = encoding.decode(content);
-    (text.into_owned(), encoding)
+fn decode_with_encoding(content: &[u8], encoding: &'static Encoding) -> (String, &'static Encoding, bool) {
+    let (text, encoding, had_errors) = encoding.decode(content);
+    (text.into_owned(), encoding, had_errors)
+}
+
+/// Matches a glob against a path relative to the input directory. Like
+/// `.gitignore`, a pattern without a `/` (e.g. `node_modules` or `*.md`)
+/// matches any single path component, while a pattern containing `/` must
+/// match the whole relative path. `*` and `?` stay within one component;
+/// `**` spans any number of them.
+fn glob_matches(pattern: &str, path: &Path) -> bool {
+    let regex = match RegexBuilder::new(&glob_to_regex(pattern.trim_start_matches('/')))
+        .case_insensitive(true)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(_) => return false,
+    };
+
+    let components: Vec<String> = path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    if pattern.contains('/') {
+        return regex.is_match(&components.join("/"));
+    }
+
+    components.iter().any(|component| regex.is_match(component))
 }
 
 fn glob_to_regex(pattern: &str) -> String {
     let mut regex = String::with_capacity(pattern.len() * 2);
     regex.push('^');
-    
-    for c in pattern.chars() {
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
         match c {
-            '*' => regex.push_str(".*"),
-            '?' => regex.push('.'),
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // `**/` also matches zero directories
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
             '.' => regex.push_str("\\."),
             '\\' => regex.push_str("\\\\"),
             '+' => regex.push_str("\\+"),
@@ -217,7 +1446,9 @@ fn glob_to_regex(pattern: &str) -> String {
             _ => regex.push(c),
         }
     }
-    
+
     regex.push('$');
     regex
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests;
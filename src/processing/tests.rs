@@ -0,0 +1,248 @@
+use super::*;
+use crate::config::Config;
+
+fn preprocess(content: &str, format: DocumentFormat) -> String {
+    let processor = DocumentProcessor::new(&Config::default().processing, false);
+    processor.preprocessor.preprocess(content, &format).unwrap()
+}
+
+/// Writes `files`, as relative paths and contents, to a temporary directory
+/// and processes it with `config`. The directory lives as long as the
+/// returned guard.
+async fn process_files(config: &ProcessingConfig, files: &[(&str, &str)]) -> (tempfile::TempDir, Vec<Document>) {
+    let dir = tempfile::tempdir().unwrap();
+    for (path, contents) in files {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    let documents = DocumentProcessor::new(config, false).process_directory(dir.path()).await.unwrap();
+    (dir, documents)
+}
+
+const RST: &str = "\
+Getting Started
+===============
+
+.. note::
+
+   Requires Python 3.9.
+
+Use :class:`~app.Client` to connect.
+
+.. code-block:: python
+   :linenos:
+
+   client = Client()
+   client.connect()
+
+.. this comment is dropped
+";
+
+#[test]
+fn strips_rst_markup() {
+    let content = preprocess(RST, DocumentFormat::RestructuredText);
+
+    assert!(content.starts_with("Getting Started\n"));
+    assert!(content.contains("Requires Python 3.9."));
+    assert!(content.contains("Use app.Client to connect."));
+    assert!(content.contains("client = Client()\nclient.connect()"));
+    for markup in ["===", "..", ":class:", ":linenos:", "code-block", "comment"] {
+        assert!(!content.contains(markup), "{:?} left in {:?}", markup, content);
+    }
+}
+
+#[test]
+fn bare_names_match_any_path_component() {
+    assert!(glob_matches("node_modules", Path::new("a/node_modules/b.md")));
+    assert!(glob_matches("*.md", Path::new("docs/readme.md")));
+    assert!(!glob_matches("node_modules", Path::new("docs/readme.md")));
+    // Only whole components match
+    assert!(!glob_matches("node_modules", Path::new("a/node_modules_old/b.md")));
+}
+
+#[tokio::test]
+async fn files_over_the_size_limit_are_skipped() {
+    let mut config = Config::default().processing;
+    config.max_file_size_bytes = 1024;
+    let large = "word ".repeat(1000);
+    let (_dir, documents) = process_files(&config, &[("small.md", "# Small\n\nFits.\n"), ("large.md", &large)]).await;
+
+    assert_eq!(documents.len(), 1);
+    assert!(documents[0].path.ends_with("small.md"));
+}
+
+#[tokio::test]
+async fn max_depth_limits_the_walk_and_zero_lifts_the_limit() {
+    let files = [("top.md", "Top."), ("a/b/c/d/deep.md", "Deep.")];
+    let mut config = Config::default().processing;
+
+    config.max_depth = 2;
+    let (_dir, limited) = process_files(&config, &files).await;
+    assert_eq!(limited.len(), 1);
+    assert!(limited[0].path.ends_with("top.md"));
+
+    config.max_depth = 0;
+    let (_dir, unlimited) = process_files(&config, &files).await;
+    assert_eq!(unlimited.len(), 2);
+    assert!(unlimited.iter().any(|document| document.path.ends_with("a/b/c/d/deep.md")));
+}
+
+const ADOC: &str = "\
+= User Guide
+:toc: left
+
+== Installation
+
+[source,rust]
+----
+fn main() {
+    println!(\"hi\");
+}
+----
+
+====
+Remember to build in release mode.
+====
+";
+
+#[tokio::test]
+async fn asciidoc_titles_and_listings_become_markdown() {
+    let mut config = Config::default().processing;
+    config.include_patterns = vec!["*.adoc".to_string()];
+    let (_dir, documents) = process_files(&config, &[("guide.adoc", ADOC)]).await;
+
+    assert_eq!(documents[0].format, DocumentFormat::AsciiDoc);
+    assert_eq!(
+        documents[0].content,
+        "# User Guide\n\n## Installation\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\nRemember to build in release mode."
+    );
+}
+
+#[test]
+fn markdown_headings_and_bullets_keep_their_markers() {
+    let markdown = "\
+# Setup
+
+Install the **CLI** from [the releases](https://example.com/releases).
+
+## Options
+
+- `--verbose` prints progress
+- nested:
+  - one
+  - two
+
+1. first
+2. second
+";
+
+    assert_eq!(
+        preprocess(markdown, DocumentFormat::Markdown),
+        "\
+# Setup
+
+Install the CLI from the releases.
+
+## Options
+
+- `--verbose` prints progress
+- nested:
+  - one
+  - two
+
+1. first
+2. second"
+    );
+}
+
+#[test]
+fn fenced_code_keeps_its_language() {
+    let markdown = "\
+Call it like this:
+
+```rust,no_run
+let client = Client::new();
+client.connect()?;
+```
+";
+
+    assert_eq!(
+        preprocess(markdown, DocumentFormat::Markdown),
+        "Call it like this:\n\n```rust\nlet client = Client::new();\nclient.connect()?;\n```"
+    );
+}
+
+#[test]
+fn globstar_star_and_question_mark() {
+    assert!(glob_matches("docs/**/*.md", Path::new("docs/index.md")));
+    assert!(glob_matches("docs/**/*.md", Path::new("docs/guides/deep/more.md")));
+    assert!(!glob_matches("docs/**/*.md", Path::new("notes/docs/index.md")));
+
+    // A single `*` stays within one directory
+    assert!(glob_matches("docs/*.md", Path::new("docs/index.md")));
+    assert!(!glob_matches("docs/*.md", Path::new("docs/guides/setup.md")));
+
+    assert!(glob_matches("v?.txt", Path::new("notes/v1.txt")));
+    assert!(!glob_matches("v?.txt", Path::new("notes/v10.txt")));
+}
+
+#[tokio::test]
+async fn patterns_match_relative_to_the_input_directory() {
+    let mut config = Config::default().processing;
+    config.include_patterns = vec!["/docs/*.md".to_string()];
+    let (_dir, documents) = process_files(&config, &[("docs/index.md", "Index."), ("src/docs/api.md", "API.")]).await;
+
+    assert_eq!(documents.len(), 1);
+    assert!(documents[0].path.ends_with("docs/index.md"));
+}
+
+#[tokio::test]
+async fn notebook_markdown_and_code_cells_both_survive() {
+    let notebook = serde_json::json!({
+        "metadata": { "kernelspec": { "language": "python" } },
+        "cells": [
+            { "cell_type": "markdown", "source": ["# Loading data\n", "Reads the CSV."] },
+            {
+                "cell_type": "code",
+                "source": "df = pd.read_csv(\"data.csv\")\ndf.shape",
+                "outputs": [
+                    { "output_type": "execute_result", "data": { "text/plain": "(120, 4)" } },
+                    { "output_type": "display_data", "data": { "image/png": "iVBORw0KGgo=", "text/plain": "<Figure>" } },
+                ],
+            },
+        ],
+    })
+    .to_string();
+
+    let mut config = Config::default().processing;
+    config.include_patterns = vec!["*.ipynb".to_string()];
+    let (_dir, documents) = process_files(&config, &[("analysis.ipynb", &notebook)]).await;
+
+    assert_eq!(documents[0].format, DocumentFormat::Notebook);
+    assert_eq!(
+        documents[0].content,
+        "# Loading data\nReads the CSV.\n\n```python\ndf = pd.read_csv(\"data.csv\")\ndf.shape\n```\n\n```\n(120, 4)\n```"
+    );
+}
+
+#[tokio::test]
+async fn mdx_imports_and_components_are_removed() {
+    let mdx = "\
+import Tabs from '@theme/Tabs';
+
+# Install
+
+<Callout type=\"warning\"/>
+
+Run the installer.
+";
+
+    let mut config = Config::default().processing;
+    config.include_patterns = vec!["*.mdx".to_string()];
+    let (_dir, documents) = process_files(&config, &[("install.mdx", mdx)]).await;
+
+    assert_eq!(documents[0].format, DocumentFormat::Markdown);
+    assert_eq!(documents[0].content, "# Install\n\nRun the installer.");
+}
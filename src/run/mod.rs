@@ -0,0 +1,951 @@
+use anyhow::{Context, Result};
+use dialoguer::MultiSelect;
+use futures::future;
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::cache::SummaryCache;
+use crate::config::Config;
+use crate::crawl::{self, CrawlOptions};
+use crate::error::DocTldrError;
+use crate::llm::{self, GeneratedSummary, IncompleteStream, LlmSummarizer, SummaryStream, TokenCounter, TokenUsage};
+use crate::output::{self, FormatOptions, OutputWriter, RunStats, StreamingOutput, Summary};
+use crate::processing::{self, Document, DocumentFormat, DocumentProcessor};
+
+/// Times a streamed summary cut off mid-response is requested again.
+const STREAM_RETRIES: usize = 2;
+
+/// Per-run settings that aren't part of [`Config`].
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Output file, or stdout when unset. With `per_input`, the directory
+    /// the per-input files are written to.
+    pub output: Option<PathBuf>,
+    /// Write one file per summary under this directory instead.
+    pub split: Option<PathBuf>,
+    /// Extra `(format, path)` outputs written from the same summaries, in
+    /// addition to the main one.
+    pub also: Vec<(String, PathBuf)>,
+    /// Write one output file per input directory.
+    pub per_input: bool,
+    /// Add to the existing `output` file instead of replacing it.
+    pub append: bool,
+    /// Format of the document read when an input is `-`.
+    pub stdin_format: String,
+    /// Order of summaries in combined output: `path`, `size` (of the
+    /// original) or `mtime`, ascending. Ties are broken by path.
+    pub sort: String,
+    /// Summarize at most this many of the matched documents, the first
+    /// ones in `sort` order.
+    pub max_files: Option<usize>,
+    /// `User-Agent` sent when fetching `http(s)://` inputs. Defaults to
+    /// `doctldr/<version>`.
+    pub user_agent: Option<String>,
+    /// Branch, tag or commit to check out for git repository inputs.
+    pub git_rev: Option<String>,
+    /// Crawl `http(s)://` inputs, following their links, instead of
+    /// fetching the single page.
+    pub crawl: Option<CrawlOptions>,
+    /// Only process files modified at or after this time.
+    pub modified_since: Option<SystemTime>,
+    /// Only process files changed relative to this git ref.
+    pub since_git: Option<String>,
+    /// Earlier JSON or JSON Lines output. Only documents that are new or
+    /// whose content changed since it was written are summarized, and the
+    /// paths it has that this run didn't find are reported as removed.
+    pub diff: Option<PathBuf>,
+    /// Report what would be summarized and the estimated cost, then stop.
+    pub dry_run: bool,
+    /// Write the prompts each document would be sent with under this
+    /// directory, then stop without calling the API.
+    pub dump_prompts: Option<PathBuf>,
+    /// Always call the LLM instead of reusing cached summaries.
+    pub no_cache: bool,
+    /// Take every summary from the cache and fail the documents that
+    /// aren't in it, without any network access. Contradicts `no_cache`.
+    pub offline: bool,
+    /// Print a [`RunStats`] report at the end.
+    pub stats: bool,
+    /// Shell command each summary is piped through before being written;
+    /// its stdout replaces the summary.
+    pub post_process: Option<String>,
+    /// Carry each document's preprocessed text in its summary's
+    /// `original_content`.
+    pub include_original: bool,
+    /// Abort on the first file that fails to be read or summarized, instead
+    /// of logging it and carrying on.
+    pub fail_fast: bool,
+    /// Let the user pick which of the matched documents to summarize before
+    /// any API calls. Requires a terminal.
+    pub interactive: bool,
+    /// Echo summaries to stderr as they stream in, when writing to stdout.
+    pub stream_to_stderr: bool,
+    pub show_progress: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            output: None,
+            split: None,
+            also: Vec::new(),
+            per_input: false,
+            append: false,
+            stdin_format: "md".to_string(),
+            sort: "path".to_string(),
+            max_files: None,
+            user_agent: None,
+            git_rev: None,
+            crawl: None,
+            modified_since: None,
+            since_git: None,
+            diff: None,
+            dry_run: false,
+            dump_prompts: None,
+            no_cache: false,
+            offline: false,
+            stats: false,
+            post_process: None,
+            include_original: false,
+            fail_fast: false,
+            interactive: false,
+            stream_to_stderr: false,
+            show_progress: false,
+        }
+    }
+}
+
+/// How many documents a run summarized, and how many failed to be read or
+/// summarized.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOutcome {
+    pub files_processed: usize,
+    pub files_failed: usize,
+}
+
+/// Summarizes every document under `inputs` and writes the result. An input
+/// of `-` reads a single document from stdin.
+///
+/// Unless `fail_fast` is set, files that fail are logged and skipped; check
+/// [`RunOutcome::files_failed`] to treat them as an error.
+pub async fn run(config: &Config, inputs: &[PathBuf], options: &RunOptions) -> Result<RunOutcome, DocTldrError> {
+    Ok(run_unclassified(config, inputs, options).await?)
+}
+
+async fn run_unclassified(config: &Config, inputs: &[PathBuf], options: &RunOptions) -> Result<RunOutcome> {
+    let started = Instant::now();
+    if options.offline {
+        if options.no_cache {
+            anyhow::bail!("--offline serves summaries from the cache, so it can't be combined with --no-cache");
+        }
+        if let Some(input) = inputs.iter().find(|input| is_url(input) || is_git_url(input)) {
+            anyhow::bail!("{} needs network access, which --offline forbids", input.display());
+        }
+    }
+    let previous = match &options.diff {
+        Some(path) => Some(output::read_summaries(path).await?),
+        None => None,
+    };
+    let sort = SortKey::from_name(&options.sort.to_lowercase())
+        .with_context(|| format!("Unsupported sort key: {} (expected path, size or mtime)", options.sort))?;
+
+    // Initialize components
+    let mut document_processor = DocumentProcessor::new(&config.processing, options.show_progress)
+        .with_fail_fast(options.fail_fast);
+    if let Some(since) = options.modified_since {
+        document_processor = document_processor.with_modified_since(since);
+    }
+    if let Some(git_ref) = &options.since_git {
+        let dirs: Vec<PathBuf> = inputs.iter()
+            .filter(|dir| !is_stdin(dir) && !is_url(dir) && !is_git_url(dir))
+            .cloned()
+            .collect();
+        let changed = processing::git_changed_paths(&dirs, git_ref).await?;
+        document_processor = document_processor.with_changed_paths(changed);
+    }
+    document_processor = document_processor.with_excluded_outputs(excluded_outputs(inputs, options));
+
+    let cache = if options.no_cache {
+        None
+    } else {
+        Some(SummaryCache::new(SummaryCache::default_dir()?, config.default.model.clone()))
+    };
+    let summarizer = match cache {
+        Some(cache) if options.offline => LlmSummarizer::offline(config, cache)?,
+        cache => LlmSummarizer::new(llm::provider_from_config(config)?, config, cache)?,
+    };
+
+    let format_options = FormatOptions {
+        include_metadata: config.output.include_metadata,
+        numbered: config.output.numbered,
+        group_by_directory: config.output.group_by_directory,
+        heading_template: config.output.heading_template.clone(),
+        separator: config.output.separator.clone(),
+    };
+    let output_writer = OutputWriter::with_options(config.output_format(), &format_options).map_err(DocTldrError::Output)?;
+    // Built now so an unknown format fails before any API calls
+    let also_writers = options.also
+        .iter()
+        .map(|(format, path)| Ok((OutputWriter::with_options(format, &format_options)?, path)))
+        .collect::<Result<Vec<_>>>()
+        .map_err(DocTldrError::Output)?;
+    let http = http_client(config, options)?;
+
+    // Walk the inputs concurrently; results come back in input order
+    let walked = future::join_all(
+        inputs.iter().map(|dir| load_input(&document_processor, &http, dir, config, options)),
+    )
+    .await;
+
+    // Remember which input each document came from
+    let mut documents = Vec::new();
+    let mut origins = HashMap::new();
+    for (index, dir_documents) in walked.into_iter().enumerate() {
+        let dir_documents = dir_documents?;
+        for document in &dir_documents {
+            origins.insert(document.path.clone(), index);
+        }
+        documents.extend(dir_documents);
+    }
+
+    let removed = match &previous {
+        Some(previous) => {
+            let (changed, removed) = diff_documents(documents, previous);
+            documents = changed;
+            removed
+        }
+        None => Vec::new(),
+    };
+
+    if let Some(max_files) = options.max_files.filter(|&max| documents.len() > max) {
+        info!("Limiting the run to {} of {} files", max_files, documents.len());
+        sort_documents(&mut documents, sort);
+        documents.truncate(max_files);
+    }
+
+    if options.interactive {
+        documents = select_documents(documents)?;
+        if documents.is_empty() {
+            warn!("No files selected; nothing to summarize");
+            return Ok(RunOutcome {
+                files_processed: 0,
+                files_failed: document_processor.failed_count(),
+            });
+        }
+    }
+
+    if let Some(dir) = &options.dump_prompts {
+        dump_prompts(&summarizer, &documents, dir).await?;
+    }
+
+    if options.dry_run {
+        let tokens = TokenCounter::for_model(&config.default.model)?;
+        let mut total_input_tokens = 0;
+        for document in &documents {
+            let input_tokens = tokens.count(&document.content);
+            total_input_tokens += input_tokens;
+            println!("Would process: {} ({} tokens)", document.path.display(), input_tokens);
+        }
+
+        // Output is bounded by max_tokens per document, so this is an upper estimate
+        let max_output_tokens = documents.len() * summarizer.max_tokens();
+        let cost = total_input_tokens as f64 / 1000.0 * config.api.price_per_1k_input
+            + max_output_tokens as f64 / 1000.0 * config.api.price_per_1k_output;
+        println!("{} files would be processed", documents.len());
+        println!("Total input tokens: {}", total_input_tokens);
+        println!("Estimated cost: up to ${:.2} (at most {} output tokens)", cost, max_output_tokens);
+    }
+
+    if options.dry_run || options.dump_prompts.is_some() {
+        return Ok(RunOutcome {
+            files_processed: 0,
+            files_failed: document_processor.failed_count(),
+        });
+    }
+
+    // Generate summaries. In verbose mode with stdout output, stream each
+    // summary to stderr as it arrives so long documents don't look hung.
+    // An output path with placeholders names one file per summary
+    let template = options.output.as_deref()
+        .and_then(Path::to_str)
+        .filter(|path| !options.per_input && output::is_template(path));
+
+    if options.append && template.is_some() {
+        anyhow::bail!("--append can't be used with an output path template");
+    }
+
+    let mut sink = match &options.output {
+        Some(path) if !options.per_input && template.is_none() => Some(if options.append {
+            output_writer.open_appending(path).await.map_err(DocTldrError::Output)?
+        } else {
+            output_writer.open_streaming(path).await.map_err(DocTldrError::Output)?
+        }),
+        _ => None,
+    };
+    let mut all_summaries = if options.stream_to_stderr && options.output.is_none() {
+        summarize_streaming(&summarizer, &documents, options).await?
+    } else {
+        let progress = progress_bar(documents.len(), options.show_progress)?;
+        let summaries = summarize_concurrently(
+            &summarizer,
+            &documents,
+            config.processing.llm_concurrency,
+            &progress,
+            sink.as_mut(),
+            options,
+        )
+        .await?;
+        progress.finish_and_clear();
+        summaries
+    };
+
+    // Completion order is arbitrary, so sort for stable output
+    sort_summaries(&mut all_summaries, sort, &documents);
+
+    let outcome = RunOutcome {
+        files_processed: all_summaries.len(),
+        files_failed: document_processor.failed_count() + documents.len() - all_summaries.len(),
+    };
+
+    let stats = if options.stats {
+        let cache = summarizer.cache_stats();
+        Some(RunStats {
+            files_processed: outcome.files_processed,
+            files_skipped: document_processor.skipped_count(),
+            files_failed: outcome.files_failed,
+            input_bytes: documents.iter().map(|d| d.metadata.file_size).sum(),
+            summary_bytes: all_summaries.iter().map(|s| s.metadata.summary_size).sum(),
+            average_compression_ratio: if all_summaries.is_empty() {
+                0.0
+            } else {
+                all_summaries.iter().map(|s| s.metadata.compression_ratio).sum::<f64>() / all_summaries.len() as f64
+            },
+            input_tokens: all_summaries.iter().map(|s| s.metadata.input_tokens).sum(),
+            output_tokens: all_summaries.iter().map(|s| s.metadata.output_tokens).sum(),
+            cached: cache.hits,
+            generated: cache.misses,
+            cost_saved: cache.saved.input_tokens as f64 / 1000.0 * config.api.price_per_1k_input
+                + cache.saved.output_tokens as f64 / 1000.0 * config.api.price_per_1k_output,
+            elapsed_secs: 0.0,
+        })
+    } else {
+        None
+    };
+
+    // Write output; the extra outputs first, since the main one may take
+    // the summaries
+    let written: Result<()> = async {
+        for (writer, path) in &also_writers {
+            writer.write_file(&all_summaries, path).await?;
+        }
+        match (&options.split, template, sink) {
+            (Some(dir), _, _) => {
+                output_writer.write_split(&all_summaries, dir).await?;
+                output_writer.write_manifest(&all_summaries, dir).await?;
+            }
+            (None, _, _) if options.per_input => {
+                let out_dir = options.output.as_deref().unwrap_or(Path::new("."));
+                write_per_input(&output_writer, inputs, &origins, all_summaries, out_dir).await?;
+            }
+            (None, Some(template), _) => {
+                output_writer.write_templated(all_summaries, template, &config.default.model).await?;
+            }
+            (None, None, Some(sink)) => sink.finish(all_summaries).await?,
+            (None, None, None) => output_writer.write(all_summaries, None).await?,
+        }
+        Ok(())
+    }
+    .await;
+    written.map_err(DocTldrError::Output)?;
+
+    if let Some(path) = &options.diff {
+        let mut report = format!("Removed since {}:", path.display());
+        for removed in &removed {
+            report.push_str(&format!("\n  {}", removed));
+        }
+        if removed.is_empty() {
+            report.push_str(" none");
+        }
+        // Like the stats report, keep this out of summaries written to stdout
+        if options.output.is_some() || options.split.is_some() || options.per_input {
+            println!("{}", report);
+        } else {
+            eprintln!("{}", report);
+        }
+    }
+
+    if let Some(mut stats) = stats {
+        stats.elapsed_secs = started.elapsed().as_secs_f64();
+        let report = stats.render(config.output_format())?;
+        // Keep the report out of summaries written to stdout
+        if options.output.is_some() || options.split.is_some() || options.per_input {
+            println!("{}", report);
+        } else {
+            eprintln!("{}", report);
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Keeps the documents that `previous` has no summary of, or whose content
+/// hash differs from the one recorded there, and lists the paths `previous`
+/// summarized that aren't among `documents`, in its order.
+fn diff_documents(documents: Vec<Document>, previous: &[Summary]) -> (Vec<Document>, Vec<String>) {
+    let hashes: HashMap<&str, &str> = previous
+        .iter()
+        .map(|summary| (summary.original_path.as_str(), summary.metadata.content_hash.as_str()))
+        .collect();
+    let current: HashSet<String> = documents.iter().map(|document| document.path.to_string_lossy().into_owned()).collect();
+    let removed = previous
+        .iter()
+        .filter(|summary| !current.contains(&summary.original_path))
+        .map(|summary| summary.original_path.clone())
+        .collect();
+
+    let total = documents.len();
+    let changed: Vec<Document> = documents
+        .into_iter()
+        .filter(|document| {
+            // Output without hashes can't show a document is unchanged
+            match hashes.get(document.path.to_string_lossy().as_ref()) {
+                Some(hash) => hash.is_empty() || *hash != document.content_hash(),
+                None => true,
+            }
+        })
+        .collect();
+    info!("Skipping {} of {} files unchanged since the previous output", total - changed.len(), total);
+
+    (changed, removed)
+}
+
+/// Reads the documents of one input: a directory, merged into a single
+/// document with `output.merge`, or stdin for `-`.
+async fn load_input(
+    processor: &DocumentProcessor,
+    http: &reqwest::Client,
+    dir: &Path,
+    config: &Config,
+    options: &RunOptions,
+) -> Result<Vec<Document>> {
+    if is_stdin(dir) {
+        let format = DocumentFormat::from_name(&options.stdin_format.to_lowercase())
+            .with_context(|| format!("Unsupported stdin format: {}", options.stdin_format))?;
+        return Ok(vec![processor.process_stdin(format).await?]);
+    }
+    let documents = if is_git_url(dir) {
+        load_git_repo(processor, dir, options).await?
+    } else {
+        match is_url(dir).then(|| dir.to_string_lossy()) {
+            Some(url) => match &options.crawl {
+                Some(crawl) => crawl::crawl(processor, http, &url, &user_agent(options), crawl).await?,
+                None => return Ok(vec![processor.process_url(&url, http).await?]),
+            },
+            None => processor.process_directory(dir).await?,
+        }
+    };
+    if config.output.merge && !documents.is_empty() {
+        Ok(vec![Document::merge(dir, documents)])
+    } else {
+        Ok(documents)
+    }
+}
+
+/// Shallow-clones the repository at `url` and processes the checkout like
+/// a directory. Paths are reported under the URL, e.g.
+/// `https://github.com/org/repo/docs/api.md`. The checkout is removed
+/// afterwards.
+async fn load_git_repo(processor: &DocumentProcessor, url: &Path, options: &RunOptions) -> Result<Vec<Document>> {
+    let checkout = processing::clone_git_repo(&url.to_string_lossy(), options.git_rev.as_deref()).await?;
+    let mut documents = processor.process_directory(checkout.path()).await?;
+    for document in &mut documents {
+        if let Ok(relative) = document.path.strip_prefix(checkout.path()) {
+            document.path = url.join(relative);
+        }
+    }
+    Ok(documents)
+}
+
+/// Shows a multi-select of `documents`, all selected to start, and keeps
+/// the chosen ones. Cancelling the prompt selects nothing.
+fn select_documents(documents: Vec<Document>) -> Result<Vec<Document>> {
+    if documents.is_empty() {
+        return Ok(documents);
+    }
+
+    let labels: Vec<String> = documents
+        .iter()
+        .map(|document| format!("{} ({} words)", document.path.display(), document.metadata.word_count))
+        .collect();
+    let selected: HashSet<usize> = MultiSelect::new()
+        .with_prompt("Files to summarize (space toggles, enter confirms)")
+        .items(&labels)
+        .defaults(&vec![true; labels.len()])
+        .interact_opt()
+        .context("File selection failed")?
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    Ok(documents
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| selected.contains(index))
+        .map(|(_, document)| document)
+        .collect())
+}
+
+/// Writes `<name>.<ext>` into `out_dir` for each input directory, holding
+/// the summaries of the documents found under it.
+async fn write_per_input(
+    output_writer: &OutputWriter,
+    inputs: &[PathBuf],
+    origins: &HashMap<PathBuf, usize>,
+    summaries: Vec<Summary>,
+    out_dir: &Path,
+) -> Result<()> {
+    tokio::fs::create_dir_all(out_dir).await
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+    let mut groups: Vec<Vec<Summary>> = inputs.iter().map(|_| Vec::new()).collect();
+    for summary in summaries {
+        if let Some(&index) = origins.get(Path::new(&summary.original_path)) {
+            groups[index].push(summary);
+        }
+    }
+
+    let mut used = HashSet::new();
+    for (input, group) in inputs.iter().zip(groups) {
+        let name = input_name(input);
+        let mut file_name = format!("{}.{}", name, output_writer.extension());
+        // Distinct inputs can share a name, e.g. `a/docs` and `b/docs`
+        let mut suffix = 2;
+        while !used.insert(file_name.clone()) {
+            file_name = format!("{}-{}.{}", name, suffix, output_writer.extension());
+            suffix += 1;
+        }
+        output_writer.write(group, Some(&out_dir.join(file_name))).await?;
+    }
+
+    Ok(())
+}
+
+/// The files and directories this run writes, canonicalized, so a rerun
+/// doesn't summarize its own earlier output. A templated `--output` names
+/// its files per summary and can't be excluded up front; if it writes
+/// inside an input directory, a warning is logged instead.
+fn excluded_outputs(inputs: &[PathBuf], options: &RunOptions) -> Vec<PathBuf> {
+    let input_dirs: Vec<PathBuf> = inputs.iter()
+        .filter(|input| !is_stdin(input) && !is_url(input) && !is_git_url(input))
+        .filter_map(|input| input.canonicalize().ok())
+        .collect();
+    let containing = |path: &Path| input_dirs.iter().find(|input| path.starts_with(input));
+
+    let mut outputs = Vec::new();
+    if let Some(output) = &options.output {
+        match output.to_str().filter(|path| !options.per_input && output::is_template(path)) {
+            Some(template) => {
+                // The directory part before the first placeholder is fixed
+                let fixed = &template[..template.find('{').unwrap_or(template.len())];
+                let dir = match fixed.rfind(['/', '\\']) {
+                    Some(end) => Path::new(&fixed[..=end]),
+                    None => Path::new("."),
+                };
+                if let Some(input) = resolve_path(dir).as_deref().and_then(containing) {
+                    warn!(
+                        "Output template {} writes inside input {}; add an --exclude pattern so its summaries aren't summarized on the next run",
+                        template,
+                        input.display()
+                    );
+                }
+            }
+            None => outputs.push(output.clone()),
+        }
+    }
+    outputs.extend(options.split.iter().cloned());
+    outputs.extend(options.also.iter().map(|(_, path)| path.clone()));
+    outputs.extend(options.dump_prompts.iter().cloned());
+
+    let outputs: Vec<PathBuf> = outputs.iter().filter_map(|path| resolve_path(path)).collect();
+    for output in &outputs {
+        if let Some(input) = containing(output) {
+            info!("{} is inside input {}; skipping it when reading inputs", output.display(), input.display());
+        }
+    }
+    outputs
+}
+
+/// Canonicalizes `path`, which may not exist yet: its nearest existing
+/// ancestor is canonicalized and the rest appended.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let mut existing = path.as_path();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        missing.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+
+    let mut resolved = existing.canonicalize().ok()?;
+    resolved.extend(missing.into_iter().rev());
+    Some(resolved)
+}
+
+/// The directory's own name, resolving `.` and `..` to the real name.
+fn input_name(input: &Path) -> String {
+    if is_stdin(input) {
+        return "stdin".to_string();
+    }
+    // The last URL segment, or the host for a bare domain
+    if is_url(input) || is_git_url(input) {
+        if let Some(name) = input.file_name() {
+            let name = name.to_string_lossy();
+            return name.strip_suffix(".git").unwrap_or(&name).to_string();
+        }
+    }
+
+    input.canonicalize()
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "summaries".to_string())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SortKey {
+    Path,
+    Size,
+    Mtime,
+}
+
+impl SortKey {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(Self::Path),
+            "size" => Some(Self::Size),
+            "mtime" => Some(Self::Mtime),
+            _ => None,
+        }
+    }
+
+    /// Orders `(path, size, mtime)` ascending by the key, then by path.
+    /// A missing modification time (stdin, URLs) sorts first.
+    fn compare(
+        self,
+        a: (&str, u64, Option<SystemTime>),
+        b: (&str, u64, Option<SystemTime>),
+    ) -> std::cmp::Ordering {
+        let by_key = match self {
+            Self::Path => std::cmp::Ordering::Equal,
+            Self::Size => a.1.cmp(&b.1),
+            Self::Mtime => a.2.cmp(&b.2),
+        };
+        by_key.then_with(|| a.0.cmp(b.0))
+    }
+}
+
+fn sort_documents(documents: &mut [Document], key: SortKey) {
+    documents.sort_by(|a, b| key.compare(document_fields(a), document_fields(b)));
+}
+
+fn document_fields(document: &Document) -> (&str, u64, Option<SystemTime>) {
+    (document.path.to_str().unwrap_or_default(), document.metadata.file_size, document.metadata.modified)
+}
+
+fn sort_summaries(summaries: &mut [Summary], key: SortKey, documents: &[Document]) {
+    let modified: HashMap<&Path, Option<SystemTime>> = documents
+        .iter()
+        .map(|document| (document.path.as_path(), document.metadata.modified))
+        .collect();
+    let modified_time = |summary: &Summary| modified.get(Path::new(&summary.original_path)).copied().flatten();
+
+    summaries.sort_by(|a, b| {
+        key.compare(
+            (&a.original_path, a.metadata.original_size, modified_time(a)),
+            (&b.original_path, b.metadata.original_size, modified_time(b)),
+        )
+    });
+}
+
+/// `-` in place of an input directory means stdin.
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Hosts whose `https://host/owner/repo` URLs name a git repository rather
+/// than a web page.
+const GIT_HOSTS: &[&str] = &["github.com", "gitlab.com", "bitbucket.org", "codeberg.org"];
+
+/// Git remotes: `git@host:path`, `git://` and `ssh://` URLs, http(s) URLs
+/// ending in `.git`, and repository roots on well-known hosts.
+fn is_git_url(path: &Path) -> bool {
+    let Some(path) = path.to_str() else {
+        return false;
+    };
+    if ["git@", "git://", "ssh://"].iter().any(|prefix| path.starts_with(prefix)) {
+        return true;
+    }
+    if !is_url(Path::new(path)) {
+        return false;
+    }
+
+    let Ok(url) = reqwest::Url::parse(path) else {
+        return false;
+    };
+    let segments: Vec<&str> = url.path().split('/').filter(|segment| !segment.is_empty()).collect();
+    url.path().ends_with(".git")
+        || (url.host_str().is_some_and(|host| GIT_HOSTS.contains(&host.trim_start_matches("www.")))
+            && segments.len() == 2)
+}
+
+fn user_agent(options: &RunOptions) -> String {
+    options.user_agent.clone()
+        .unwrap_or_else(|| format!("doctldr/{}", env!("CARGO_PKG_VERSION")))
+}
+
+/// Client for `http(s)://` inputs, with the API proxy and timeouts and up
+/// to ten redirects. `api.extra_headers` are only sent to the API.
+fn http_client(config: &Config, options: &RunOptions) -> Result<reqwest::Client> {
+    llm::with_proxy(reqwest::Client::builder(), &config.api)?
+        .user_agent(user_agent(options))
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(Duration::from_secs(config.api.timeout_secs))
+        .connect_timeout(Duration::from_secs(config.api.connect_timeout_secs))
+        .build()
+        .context("Failed to build HTTP client")
+}
+
+fn progress_bar(len: usize, show: bool) -> Result<ProgressBar> {
+    if !show {
+        return Ok(ProgressBar::hidden());
+    }
+
+    Ok(ProgressBar::new(len as u64)
+        .with_style(ProgressStyle::with_template("[{pos}/{len}] {bar:30} {wide_msg}")?))
+}
+
+/// Summarizes documents a bounded number at a time, logging failures.
+/// Small documents are batched as [`LlmSummarizer::batches`] decides, when
+/// the provider supports batching. Each summary is passed to `sink` as soon
+/// as it completes.
+async fn summarize_concurrently(
+    summarizer: &LlmSummarizer,
+    documents: &[Document],
+    concurrency: usize,
+    progress: &ProgressBar,
+    mut sink: Option<&mut StreamingOutput<'_>>,
+    options: &RunOptions,
+) -> Result<Vec<Summary>> {
+    let groups = if summarizer.supports_batch() {
+        let batchable: Vec<_> = documents.iter()
+            .map(|document| (document.content.as_str(), Some(document.format)))
+            .collect();
+        summarizer.batches(&batchable)
+    } else {
+        (0..documents.len()).map(|index| vec![index]).collect()
+    };
+    let mut results = stream::iter(groups)
+        .map(|batch| async move {
+            let batch: Vec<&Document> = batch.into_iter().map(|index| &documents[index]).collect();
+            progress.set_message(batch[0].path.display().to_string());
+            let generated = match batch.as_slice() {
+                [document] => vec![summarizer.summarize(&document.content, Some(document.format)).await.map_err(Into::into)],
+                _ => {
+                    let contents: Vec<&str> = batch.iter().map(|document| document.content.as_str()).collect();
+                    summarizer.summarize_batch(&contents, Some(batch[0].format)).await
+                }
+            };
+
+            let mut results = Vec::with_capacity(batch.len());
+            for (document, generated) in batch.into_iter().zip(generated) {
+                let result = match generated {
+                    Ok(generated) => Ok(finish_summary(document, generated, options).await),
+                    Err(e) => Err(e),
+                };
+                progress.inc(1);
+                results.push((document, result));
+            }
+            results
+        })
+        .buffer_unordered(concurrency.max(1))
+        .flat_map(stream::iter);
+
+    let mut summaries = Vec::new();
+    while let Some((document, result)) = results.next().await {
+        match result {
+            Ok(summary) => {
+                if let Some(sink) = sink.as_deref_mut() {
+                    sink.write_one(&summary).await.map_err(DocTldrError::Output)?;
+                }
+                summaries.push(summary);
+            }
+            Err(e) if options.fail_fast => {
+                return Err(e.context(format!("Failed to summarize {}", document.path.display())));
+            }
+            Err(e) => warn!("Failed to summarize {}: {}", document.path.display(), e),
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Builds the summary of `document`, attaching its original text and
+/// running the post-process command as `options` ask.
+async fn finish_summary(document: &Document, generated: GeneratedSummary, options: &RunOptions) -> Summary {
+    let mut summary = Summary::new(document, generated);
+    if options.include_original {
+        summary.original_content = Some(document.content.clone());
+    }
+    if let Some(command) = &options.post_process {
+        post_process_summary(command, &mut summary).await;
+    }
+    summary
+}
+
+/// Pipes the summary text through `command`, run by the shell with
+/// `DOCTLDR_PATH` set to the original path, and keeps its stdout. If the
+/// command can't be run, exits non-zero or prints invalid UTF-8, the
+/// summary is kept as is with a warning.
+async fn post_process_summary(command: &str, summary: &mut Summary) {
+    match run_post_process(command, summary).await {
+        Ok(text) => summary.replace_text(text),
+        Err(e) => warn!("Post-processing {} failed, keeping the original summary: {}", summary.original_path, e),
+    }
+}
+
+async fn run_post_process(command: &str, summary: &Summary) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = tokio::process::Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = tokio::process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .env("DOCTLDR_PATH", &summary.original_path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+
+    // Written from a separate task so a command that prints before reading
+    // all of its input can't deadlock against us
+    let mut stdin = child.stdin.take().context("No stdin for post-process command")?;
+    let input = summary.summary.clone();
+    let writer = tokio::spawn(async move { stdin.write_all(input.as_bytes()).await });
+
+    let output = child.wait_with_output().await?;
+    // A command that exits without reading its input closes the pipe early
+    let _ = writer.await;
+    if !output.status.success() {
+        anyhow::bail!("{}", output.status);
+    }
+    String::from_utf8(output.stdout).context("Output is not valid UTF-8")
+}
+
+/// Writes the messages each document's first requests would carry to
+/// `<dir>/<path>.prompt.txt`, one section per message and, for chunked
+/// documents, one block per chunk.
+async fn dump_prompts(summarizer: &LlmSummarizer, documents: &[Document], dir: &Path) -> Result<()> {
+    for document in documents {
+        let requests = summarizer.build_prompts(&document.content, Some(document.format))?;
+        let mut text = String::new();
+        for (index, messages) in requests.iter().enumerate() {
+            if requests.len() > 1 {
+                text.push_str(&format!("===== chunk {} of {} =====\n\n", index + 1, requests.len()));
+            }
+            for message in messages {
+                text.push_str(&format!("--- {} ---\n{}\n\n", message.role, message.content));
+            }
+        }
+
+        let path = output::nested_path(&document.path, dir, ".prompt.txt");
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        tokio::fs::write(&path, text).await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    info!("Written {} prompts to {}", documents.len(), dir.display());
+    Ok(())
+}
+
+/// Summarizes documents one at a time, echoing chunks to stderr as they arrive.
+async fn summarize_streaming(
+    summarizer: &LlmSummarizer,
+    documents: &[Document],
+    options: &RunOptions,
+) -> Result<Vec<Summary>> {
+    let mut summaries = Vec::new();
+
+    for document in documents {
+        eprintln!("==> {}", document.path.display());
+        match stream_summary(summarizer, document).await {
+            Ok(generated) => summaries.push(finish_summary(document, generated, options).await),
+            Err(e) if options.fail_fast => {
+                return Err(e.context(format!("Failed to summarize {}", document.path.display())));
+            }
+            Err(e) => warn!("Failed to summarize {}: {}", document.path.display(), e),
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Streams one summary to stderr. A stream cut off mid-summary is
+/// requested again from the start, up to [`STREAM_RETRIES`] times; its
+/// partial text is discarded. The self-check and length checks apply once
+/// the stream ends, so the recorded summary may differ from the echoed one.
+async fn stream_summary(summarizer: &LlmSummarizer, document: &Document) -> Result<GeneratedSummary> {
+    let mut spent = TokenUsage::default();
+    let mut attempt = 0;
+
+    loop {
+        let SummaryStream { input, chunks, model, max_tokens, mut usage, mut stream } =
+            summarizer.summarize_stream(&document.content, Some(document.format)).await?;
+        let mut text = String::new();
+        let mut stderr = io::stderr();
+
+        let mut interrupted = None;
+        while let Some(piece) = stream.next().await {
+            match piece {
+                Ok(piece) => {
+                    eprint!("{}", piece);
+                    stderr.flush()?;
+                    text.push_str(&piece);
+                }
+                Err(e) if e.is::<IncompleteStream>() && attempt < STREAM_RETRIES => {
+                    interrupted = Some(e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        eprintln!();
+
+        usage.output_tokens += summarizer.count_tokens(&text);
+        spent += usage;
+        if let Some(e) = interrupted {
+            attempt += 1;
+            warn!("{}: {}; retrying ({}/{})", document.path.display(), e, attempt, STREAM_RETRIES);
+            continue;
+        }
+
+        let streamed = GeneratedSummary { text, chunks, model, usage: spent, retried: false, max_tokens };
+        return summarizer.finish_stream(&input, Some(document.format), streamed).await;
+    }
+}
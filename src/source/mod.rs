@@ -0,0 +1,641 @@
+//! Reducing source files to their documented interface: doc comments and
+//! the signatures of public items, without implementation bodies.
+
+use anyhow::Result;
+use regex::Regex;
+
+/// Programming languages whose source files can be summarized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceLanguage {
+    Rust,
+    Python,
+}
+
+impl SourceLanguage {
+    /// Parses a file extension or language name such as `rs` or `python`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rs" | "rust" => Some(Self::Rust),
+            "py" | "pyi" | "python" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    /// The extractor for this language's source files.
+    pub fn extractor(self) -> &'static dyn DocExtractor {
+        match self {
+            Self::Rust => &RustExtractor,
+            Self::Python => &PythonExtractor,
+        }
+    }
+}
+
+/// Pulls the doc comments and public signatures out of one language's
+/// source. Supporting another language takes an implementation of this and
+/// a [`SourceLanguage`] variant naming its extensions.
+pub trait DocExtractor: Send + Sync {
+    fn extract(&self, source: &str) -> Result<String>;
+}
+
+/// Keeps `///` and `//!` comments and the signatures of `pub` items, trait
+/// items and trait impls. Function bodies, private items, non-`pub` struct
+/// fields, attributes and ordinary comments are dropped; a signature
+/// ending in a body is closed with `;` instead.
+pub struct RustExtractor;
+
+#[derive(Clone, Copy)]
+enum RustBlock {
+    /// `impl`, `trait` and `mod` bodies. In trait definitions and trait
+    /// impls every item is part of the interface, `pub` or not.
+    Items { all_public: bool },
+    /// Struct and union fields, or enum variants when `all_public`.
+    Fields { all_public: bool },
+}
+
+/// An open `{` block and the lines opening it, which are only written out
+/// once something inside the block is kept.
+struct OpenBlock {
+    kind: RustBlock,
+    header: Vec<String>,
+    emitted: bool,
+}
+
+/// How a Rust item's signature ended.
+enum SignatureEnd {
+    /// At a `{`, leaving this many braces open after the signature's last
+    /// line; `0` when the block closed on that line.
+    Block(usize),
+    /// At a `;`, or the end of the file.
+    Statement,
+}
+
+struct Signature {
+    /// The signature's lines, the last one cut before the `{`, `;` or `=`.
+    text: String,
+    /// The signature's lines in full.
+    lines: Vec<String>,
+    end: SignatureEnd,
+}
+
+impl DocExtractor for RustExtractor {
+    fn extract(&self, source: &str) -> Result<String> {
+        let item = Regex::new(
+            r#"^(pub(\s*\([^)]*\))?\s+)?((default|const|async|unsafe|extern(\s+"[^"]*")?)\s+)*(?P<keyword>fn|struct|enum|union|trait|impl|mod|type|const|static|macro_rules!|use)\b"#,
+        )?;
+        let mut lines = source.lines();
+        let mut lexer = RustLexer::default();
+        let mut output: Vec<String> = Vec::new();
+        let mut blocks: Vec<OpenBlock> = Vec::new();
+        let mut docs: Vec<String> = Vec::new();
+
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim();
+
+            // Inside a block comment or string carried over from a skipped line
+            if lexer.in_literal() {
+                lexer.punctuation(line);
+                continue;
+            }
+            if trimmed.starts_with("//!") {
+                emit_headers(&mut output, &mut blocks);
+                output.push(line.trim_end().to_string());
+                continue;
+            }
+            if trimmed.starts_with("///") && !trimmed.starts_with("////") {
+                docs.push(line.trim_end().to_string());
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with("//") {
+                continue;
+            }
+            if trimmed.starts_with("#[") || trimmed.starts_with("#!") {
+                skip_nested(line, &mut lines, &mut lexer);
+                continue;
+            }
+
+            if let Some(OpenBlock { kind: RustBlock::Fields { all_public }, .. }) = blocks.last() {
+                let all_public = *all_public;
+                let balance = brace_balance(&lexer.punctuation(line));
+                if balance < 0 {
+                    close_block(&mut output, &mut blocks, line);
+                    docs.clear();
+                    continue;
+                }
+
+                let keep = all_public || is_pub(trimmed);
+                if keep {
+                    output.append(&mut docs);
+                    output.push(line.trim_end().to_string());
+                }
+                docs.clear();
+                // A struct-like variant spanning several lines
+                if balance > 0 {
+                    let body = skip_block(balance as usize, &mut lines, &mut lexer);
+                    if keep {
+                        output.extend(body);
+                    }
+                }
+                continue;
+            }
+
+            let Some(keyword) = item.captures(trimmed).map(|caps| caps["keyword"].to_string()) else {
+                // Statements and macro invocations, along with any block
+                // they open
+                let balance = brace_balance(&lexer.punctuation(line));
+                if balance < 0 {
+                    close_block(&mut output, &mut blocks, line);
+                } else if balance > 0 {
+                    skip_block(balance as usize, &mut lines, &mut lexer);
+                }
+                docs.clear();
+                continue;
+            };
+
+            let in_trait = matches!(blocks.last(), Some(OpenBlock { kind: RustBlock::Items { all_public: true }, .. }));
+            let visible = in_trait || is_pub(trimmed);
+            let signature = read_signature(line, &mut lines, &mut lexer, matches!(keyword.as_str(), "const" | "static"));
+            let open = match signature.end {
+                SignatureEnd::Block(open) => Some(open),
+                SignatureEnd::Statement => None,
+            };
+
+            match keyword.as_str() {
+                // Their items decide whether the block is written out
+                "impl" | "mod" if open.is_some_and(|open| open > 0) && (keyword == "impl" || visible) => {
+                    let mut header = std::mem::take(&mut docs);
+                    header.push(format!("{} {{", signature.text));
+                    blocks.push(OpenBlock {
+                        kind: RustBlock::Items {
+                            all_public: keyword == "impl" && signature.text.contains(" for "),
+                        },
+                        header,
+                        emitted: false,
+                    });
+                }
+                "struct" | "union" | "enum" | "trait" if visible && open.is_some_and(|open| open > 0) => {
+                    keep_item(&mut output, &mut blocks, &mut docs, [format!("{} {{", signature.text)]);
+                    let kind = match keyword.as_str() {
+                        "trait" => RustBlock::Items { all_public: true },
+                        keyword => RustBlock::Fields { all_public: keyword == "enum" },
+                    };
+                    blocks.push(OpenBlock { kind, header: Vec::new(), emitted: true });
+                }
+                "fn" | "const" | "static" if visible => {
+                    keep_item(&mut output, &mut blocks, &mut docs, [format!("{};", signature.text)]);
+                    if let Some(open) = open.filter(|open| *open > 0) {
+                        skip_block(open, &mut lines, &mut lexer);
+                    }
+                }
+                "macro_rules!" => {
+                    keep_item(&mut output, &mut blocks, &mut docs, [format!("{} {{ ... }}", signature.text)]);
+                    if let Some(open) = open.filter(|open| *open > 0) {
+                        skip_block(open, &mut lines, &mut lexer);
+                    }
+                }
+                "use" if visible => {
+                    let mut kept = signature.lines;
+                    if let Some(open) = open.filter(|open| *open > 0) {
+                        kept.extend(skip_block(open, &mut lines, &mut lexer));
+                    }
+                    keep_item(&mut output, &mut blocks, &mut docs, kept);
+                }
+                "impl" | "mod" if visible && keyword == "mod" && open.is_none() => {
+                    keep_item(&mut output, &mut blocks, &mut docs, signature.lines);
+                }
+                _ if visible && open.is_none_or(|open| open == 0) => {
+                    keep_item(&mut output, &mut blocks, &mut docs, signature.lines);
+                }
+                _ => {
+                    docs.clear();
+                    if let Some(open) = open.filter(|open| *open > 0) {
+                        skip_block(open, &mut lines, &mut lexer);
+                    }
+                }
+            }
+        }
+
+        Ok(output.join("\n"))
+    }
+}
+
+fn is_pub(item: &str) -> bool {
+    item.starts_with("pub ")
+}
+
+/// Writes out the headers of the enclosing blocks not written yet.
+fn emit_headers(output: &mut Vec<String>, blocks: &mut [OpenBlock]) {
+    for block in blocks.iter_mut().filter(|block| !block.emitted) {
+        if output.last().is_some_and(|line| !line.is_empty()) && block.header.first().is_some_and(|line| !line.starts_with(' ')) {
+            output.push(String::new());
+        }
+        output.append(&mut block.header);
+        block.emitted = true;
+    }
+}
+
+fn keep_item(
+    output: &mut Vec<String>,
+    blocks: &mut [OpenBlock],
+    docs: &mut Vec<String>,
+    lines: impl IntoIterator<Item = String>,
+) {
+    emit_headers(output, blocks);
+    // Separate top-level items
+    if blocks.is_empty() && output.last().is_some_and(|line| !line.is_empty()) {
+        output.push(String::new());
+    }
+    output.append(docs);
+    output.extend(lines);
+}
+
+fn close_block(output: &mut Vec<String>, blocks: &mut Vec<OpenBlock>, line: &str) {
+    if blocks.pop().is_some_and(|block| block.emitted) {
+        output.push(line.trim_end().to_string());
+    }
+}
+
+/// Reads an item's signature, starting at `first`, up to the `{` or `;`
+/// outside parentheses and brackets that ends it. With `at_assign` it is
+/// cut at `=` as well, and the value is skipped up to its `;`.
+fn read_signature<'a>(
+    first: &'a str,
+    lines: &mut impl Iterator<Item = &'a str>,
+    lexer: &mut RustLexer,
+    at_assign: bool,
+) -> Signature {
+    let mut text = String::new();
+    let mut full = Vec::new();
+    let mut nesting = 0usize;
+    let mut line = Some(first);
+    let mut assigned = false;
+
+    while let Some(current) = line {
+        full.push(current.trim_end().to_string());
+        let punctuation = lexer.punctuation(current);
+        for (index, &(position, c)) in punctuation.iter().enumerate() {
+            match c {
+                b'(' | b'[' => nesting += 1,
+                b')' | b']' => nesting = nesting.saturating_sub(1),
+                b'{' if assigned => nesting += 1,
+                b'}' if assigned => nesting = nesting.saturating_sub(1),
+                b'=' if at_assign && !assigned && nesting == 0 => {
+                    text.push_str(current[..position].trim_end());
+                    assigned = true;
+                }
+                b'{' if nesting == 0 => {
+                    text.push_str(current[..position].trim_end());
+                    let open = brace_balance(&punctuation[index..]).max(0) as usize;
+                    return Signature { text, lines: full, end: SignatureEnd::Block(open) };
+                }
+                b';' if nesting == 0 => {
+                    if !assigned {
+                        text.push_str(current[..position].trim_end());
+                    }
+                    return Signature { text, lines: full, end: SignatureEnd::Statement };
+                }
+                _ => {}
+            }
+        }
+        if !assigned {
+            text.push_str(current.trim_end());
+            text.push('\n');
+        }
+        line = lines.next();
+    }
+
+    Signature { text: text.trim_end().to_string(), lines: full, end: SignatureEnd::Statement }
+}
+
+/// Consumes lines until `open` braces are closed, returning them.
+fn skip_block<'a>(mut open: usize, lines: &mut impl Iterator<Item = &'a str>, lexer: &mut RustLexer) -> Vec<String> {
+    let mut skipped = Vec::new();
+    while open > 0 {
+        let Some(line) = lines.next() else {
+            break;
+        };
+        skipped.push(line.trim_end().to_string());
+        open = (open as isize + brace_balance(&lexer.punctuation(line))).max(0) as usize;
+    }
+    skipped
+}
+
+/// Consumes an attribute, which may span lines, starting at `first`.
+fn skip_nested<'a>(first: &'a str, lines: &mut impl Iterator<Item = &'a str>, lexer: &mut RustLexer) {
+    let mut nesting = 0isize;
+    let mut line = Some(first);
+    while let Some(current) = line {
+        for (_, c) in lexer.punctuation(current) {
+            match c {
+                b'(' | b'[' | b'{' => nesting += 1,
+                b')' | b']' | b'}' => nesting -= 1,
+                _ => {}
+            }
+        }
+        if nesting <= 0 {
+            return;
+        }
+        line = lines.next();
+    }
+}
+
+fn brace_balance(punctuation: &[(usize, u8)]) -> isize {
+    punctuation.iter().fold(0, |balance, &(_, c)| match c {
+        b'{' => balance + 1,
+        b'}' => balance - 1,
+        _ => balance,
+    })
+}
+
+/// Finds the punctuation that matters for structure outside comments and
+/// string and character literals, carrying block comments and strings
+/// over from one line to the next.
+#[derive(Default)]
+struct RustLexer {
+    comment_depth: usize,
+    /// Inside a string literal; `Some(n)` for a raw string closed by `"`
+    /// and `n` hashes.
+    string: Option<Option<usize>>,
+}
+
+impl RustLexer {
+    fn in_literal(&self) -> bool {
+        self.comment_depth > 0 || self.string.is_some()
+    }
+
+    /// Byte offsets of the brackets, braces, `;` and `=` in `line`.
+    fn punctuation(&mut self, line: &str) -> Vec<(usize, u8)> {
+        let bytes = line.as_bytes();
+        let mut found = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if self.comment_depth > 0 {
+                if rest.starts_with(b"*/") {
+                    self.comment_depth -= 1;
+                    i += 2;
+                } else if rest.starts_with(b"/*") {
+                    self.comment_depth += 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+            match self.string {
+                Some(None) => {
+                    match bytes[i] {
+                        b'\\' => i += 2,
+                        b'"' => {
+                            self.string = None;
+                            i += 1;
+                        }
+                        _ => i += 1,
+                    }
+                    continue;
+                }
+                Some(Some(hashes)) => {
+                    if bytes[i] == b'"' && rest[1..].iter().take_while(|&&b| b == b'#').count() >= hashes {
+                        self.string = None;
+                        i += 1 + hashes;
+                    } else {
+                        i += 1;
+                    }
+                    continue;
+                }
+                None => {}
+            }
+
+            match bytes[i] {
+                b'/' if rest.starts_with(b"//") => break,
+                b'/' if rest.starts_with(b"/*") => {
+                    self.comment_depth = 1;
+                    i += 2;
+                }
+                b'"' => {
+                    self.string = Some(None);
+                    i += 1;
+                }
+                b'r' if i == 0 || !is_ident_byte(bytes[i - 1]) => {
+                    let hashes = rest[1..].iter().take_while(|&&b| b == b'#').count();
+                    if rest.get(1 + hashes) == Some(&b'"') {
+                        self.string = Some(Some(hashes));
+                        i += 2 + hashes;
+                    } else {
+                        i += 1;
+                    }
+                }
+                b'\'' => i += char_literal_len(rest),
+                c @ (b'{' | b'}' | b'(' | b')' | b'[' | b']' | b';' | b'=') => {
+                    found.push((i, c));
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
+
+        found
+    }
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Length of the character literal at the start of `rest`, or `1` for a
+/// lifetime's quote.
+fn char_literal_len(rest: &[u8]) -> usize {
+    match rest.get(1) {
+        // '\n', '\'', '\u{1F600}'
+        Some(b'\\') => rest.iter().skip(3).position(|&b| b == b'\'').map_or(1, |end| end + 4),
+        Some(_) if rest.get(2) == Some(&b'\'') => 3,
+        // Up to four bytes of UTF-8 between the quotes
+        Some(&b) if !b.is_ascii() => (3..=5).find(|&end| rest.get(end) == Some(&b'\'')).map_or(1, |end| end + 1),
+        _ => 1,
+    }
+}
+
+/// Keeps docstrings and the signatures of classes and functions whose
+/// names don't start with `_` (dunder methods such as `__init__` are kept),
+/// with their decorators, plus annotated class attributes. Function bodies
+/// and other statements are dropped.
+pub struct PythonExtractor;
+
+impl DocExtractor for PythonExtractor {
+    fn extract(&self, source: &str) -> Result<String> {
+        let definition = Regex::new(r"^(async\s+)?(?P<keyword>def|class)\s+(?P<name>[A-Za-z_]\w*)")?;
+        let attribute = Regex::new(r"^[A-Za-z]\w*\s*:[^=]")?;
+        let lines: Vec<&str> = source.lines().collect();
+        let mut output: Vec<String> = Vec::new();
+        let mut decorators: Vec<&str> = Vec::new();
+        // Indentation of the classes the current line may be in
+        let mut classes: Vec<usize> = Vec::new();
+        // Lines indented deeper than this are a skipped body
+        let mut skip_deeper: Option<usize> = None;
+        let mut string: Option<&str> = None;
+
+        let mut i = skip_comments(&lines, 0);
+        if let Some(end) = docstring_end(&lines, i) {
+            output.extend(lines[i..end].iter().map(|line| line.trim_end().to_string()));
+            i = end;
+        }
+
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim();
+            let indent = indentation(line);
+            i += 1;
+
+            if let Some(quote) = string {
+                string = update_triple_quote(line, Some(quote));
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if skip_deeper.is_some_and(|level| indent > level) {
+                string = update_triple_quote(line, None);
+                continue;
+            }
+            skip_deeper = None;
+            while classes.last().is_some_and(|&level| indent <= level) {
+                classes.pop();
+            }
+
+            if trimmed.starts_with('@') {
+                decorators.push(line);
+                continue;
+            }
+
+            let Some(caps) = definition.captures(trimmed) else {
+                let in_class_body = classes.last().is_some_and(|&level| indent > level);
+                if in_class_body && attribute.is_match(trimmed) && update_triple_quote(line, None).is_none() {
+                    output.push(line.trim_end().to_string());
+                }
+                string = update_triple_quote(line, None);
+                decorators.clear();
+                continue;
+            };
+
+            let name = &caps["name"];
+            let is_class = &caps["keyword"] == "class";
+            let private = name.starts_with('_') && !(name.starts_with("__") && name.ends_with("__"));
+            let (header, end) = python_header(&lines, i - 1);
+            i = end;
+            if private {
+                skip_deeper = Some(indent);
+                decorators.clear();
+                continue;
+            }
+
+            if indent == 0 && output.last().is_some_and(|line| !line.is_empty()) {
+                output.push(String::new());
+            }
+            output.extend(decorators.drain(..).map(|line| line.trim_end().to_string()));
+            output.extend(header);
+
+            let body = skip_comments(&lines, i);
+            if let Some(end) = docstring_end(&lines, body).filter(|_| indentation(lines[body]) > indent) {
+                output.extend(lines[body..end].iter().map(|line| line.trim_end().to_string()));
+                i = end;
+            }
+            if is_class {
+                classes.push(indent);
+            } else {
+                skip_deeper = Some(indent);
+            }
+        }
+
+        Ok(output.join("\n"))
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The first line at or after `start` that isn't blank or a comment.
+fn skip_comments(lines: &[&str], start: usize) -> usize {
+    (start..lines.len())
+        .find(|&i| {
+            let trimmed = lines[i].trim();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        })
+        .unwrap_or(lines.len())
+}
+
+/// The line after the docstring starting at `start`, if the statement there
+/// is a string literal.
+fn docstring_end(lines: &[&str], start: usize) -> Option<usize> {
+    let trimmed = lines.get(start)?.trim();
+    let literal = trimmed.trim_start_matches(['r', 'R', 'u', 'U']);
+    let quote = ["\"\"\"", "'''", "\"", "'"].into_iter().find(|quote| literal.starts_with(quote))?;
+
+    if quote.len() == 1 {
+        return Some(start + 1);
+    }
+    if literal[3..].contains(quote) {
+        return Some(start + 1);
+    }
+    (start + 1..lines.len()).find(|&i| lines[i].contains(quote)).map(|i| i + 1)
+}
+
+/// The lines of the `def` or `class` header starting at `start`, through
+/// the `:` that ends it outside brackets, and the index after it. Code
+/// following the `:` on the same line is dropped.
+fn python_header(lines: &[&str], start: usize) -> (Vec<String>, usize) {
+    let mut header = Vec::new();
+    let mut nesting = 0usize;
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let mut quote: Option<char> = None;
+        for (position, c) in line.char_indices() {
+            match (quote, c) {
+                (Some(open), c) if c == open => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '#') => break,
+                (None, '(' | '[' | '{') => nesting += 1,
+                (None, ')' | ']' | '}') => nesting = nesting.saturating_sub(1),
+                (None, ':') if nesting == 0 => {
+                    header.push(line[..=position].to_string());
+                    return (header, i + 1);
+                }
+                _ => {}
+            }
+        }
+        header.push(line.trim_end().to_string());
+    }
+
+    (header, lines.len())
+}
+
+/// Tracks whether `line` leaves a triple-quoted string open, given the one
+/// open before it.
+fn update_triple_quote(line: &str, mut open: Option<&'static str>) -> Option<&'static str> {
+    let mut rest = line;
+    loop {
+        match open {
+            Some(quote) => match rest.find(quote) {
+                Some(end) => {
+                    rest = &rest[end + 3..];
+                    open = None;
+                }
+                None => return open,
+            },
+            None => {
+                let next = ["\"\"\"", "'''"]
+                    .into_iter()
+                    .filter_map(|quote| rest.find(quote).map(|start| (start, quote)))
+                    .min();
+                match next {
+                    Some((start, quote)) if !rest[..start].contains('#') => {
+                        rest = &rest[start + 3..];
+                        open = Some(quote);
+                    }
+                    _ => return None,
+                }
+            }
+        }
+    }
+}
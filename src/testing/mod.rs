@@ -0,0 +1,70 @@
+//! Helpers for exercising the pipeline without network access.
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::llm::{LlmProvider, SummaryResult};
+use crate::processing::DocumentFormat;
+
+/// An [`LlmProvider`] that answers locally and deterministically. It
+/// reports no token usage and `mock` as its model.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    response: MockResponse,
+}
+
+#[derive(Debug, Clone)]
+enum MockResponse {
+    Echo { prefix: String, max_chars: usize },
+    Canned(String),
+    Error(String),
+}
+
+impl MockProvider {
+    /// Summarizes as `prefix` followed by the first `max_chars` characters
+    /// of the input.
+    pub fn echo(prefix: impl Into<String>, max_chars: usize) -> Self {
+        Self {
+            response: MockResponse::Echo { prefix: prefix.into(), max_chars },
+        }
+    }
+
+    /// Returns `summary` for every input.
+    pub fn canned(summary: impl Into<String>) -> Self {
+        Self {
+            response: MockResponse::Canned(summary.into()),
+        }
+    }
+
+    /// Fails every request with `message`, like an API error would.
+    pub fn failing(message: impl Into<String>) -> Self {
+        Self {
+            response: MockResponse::Error(message.into()),
+        }
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::echo("Summary: ", 80)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    async fn summarize(&self, content: &str, _format: Option<DocumentFormat>, _max_tokens: usize) -> Result<SummaryResult> {
+        let text = match &self.response {
+            MockResponse::Echo { prefix, max_chars } => {
+                format!("{}{}", prefix, content.chars().take(*max_chars).collect::<String>())
+            }
+            MockResponse::Canned(summary) => summary.clone(),
+            MockResponse::Error(message) => anyhow::bail!("{}", message),
+        };
+
+        Ok(SummaryResult {
+            text,
+            usage: None,
+            model: "mock".to_string(),
+        })
+    }
+}
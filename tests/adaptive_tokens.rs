@@ -0,0 +1,32 @@
+use doctldr::config::Config;
+use doctldr::testing::MockProvider;
+use doctldr::LlmSummarizer;
+
+fn summarizer(adaptive: bool) -> LlmSummarizer {
+    let mut config = Config::default();
+    config.default.max_tokens = 1024;
+    config.default.adaptive_tokens = adaptive;
+    config.default.adaptive_min_tokens = 64;
+    config.validate().unwrap();
+    LlmSummarizer::new(Box::new(MockProvider::canned("Summary.")), &config, None).unwrap()
+}
+
+#[tokio::test]
+async fn budget_scales_with_input_between_the_bounds() {
+    let summarizer = summarizer(true);
+    let short = "A five line file.";
+    let medium = "The install guide covers every supported platform. ".repeat(150);
+    let long = "The install guide covers every supported platform. ".repeat(600);
+
+    assert_eq!(summarizer.summarize(short, None).await.unwrap().max_tokens, 64);
+    let budget = summarizer.summarize(&medium, None).await.unwrap().max_tokens;
+    assert_eq!(budget, summarizer.count_tokens(&medium) / 4);
+    assert!(budget > 64 && budget < 1024);
+    assert_eq!(summarizer.summarize(&long, None).await.unwrap().max_tokens, 1024);
+}
+
+#[tokio::test]
+async fn budget_is_max_tokens_without_adaptive_tokens() {
+    let summary = summarizer(false).summarize("A five line file.", None).await.unwrap();
+    assert_eq!(summary.max_tokens, 1024);
+}
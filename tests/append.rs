@@ -0,0 +1,86 @@
+mod common;
+
+use common::summary;
+use doctldr::output::Summary;
+use doctldr::OutputWriter;
+
+async fn append(format: &str, path: &std::path::Path, summaries: Vec<Summary>) {
+    let writer = OutputWriter::new(format).unwrap();
+    writer.open_appending(path).await.unwrap().finish(summaries).await.unwrap();
+}
+
+#[tokio::test]
+async fn jsonl_appends_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries.jsonl");
+
+    append("jsonl", &out, vec![summary("a.md", "A")]).await;
+    append("jsonl", &out, vec![summary("b.md", "B")]).await;
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["original_path"], "a.md");
+    assert_eq!(lines[1]["original_path"], "b.md");
+}
+
+#[tokio::test]
+async fn json_merges_and_replaces_by_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries.json");
+
+    append("json", &out, vec![summary("b.md", "old B"), summary("c.md", "C")]).await;
+    append("json", &out, vec![summary("b.md", "new B"), summary("a.md", "A")]).await;
+
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    let entries: Vec<_> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| (entry["original_path"].as_str().unwrap(), entry["summary"].as_str().unwrap()))
+        .collect();
+    assert_eq!(entries, [("a.md", "A"), ("b.md", "new B"), ("c.md", "C")]);
+}
+
+#[tokio::test]
+async fn html_cannot_be_appended_to() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries.html");
+    std::fs::write(&out, "<html></html>").unwrap();
+
+    assert!(OutputWriter::new("html").unwrap().open_appending(&out).await.is_err());
+}
+
+#[tokio::test]
+async fn json_without_metadata_can_be_appended_to() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries.json");
+    let writer = OutputWriter::with_metadata("json", false).unwrap();
+
+    writer.open_appending(&out).await.unwrap().finish(vec![summary("a.md", "A")]).await.unwrap();
+    writer.open_appending(&out).await.unwrap().finish(vec![summary("b.md", "B")]).await.unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.get("metadata").is_none()));
+}
+
+#[tokio::test]
+async fn original_content_is_kept_across_appends() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries.json");
+    let mut with_original = summary("a.md", "A");
+    with_original.original_content = Some("# A\n\nThe full text.".to_string());
+
+    append("json", &out, vec![with_original]).await;
+    append("json", &out, vec![summary("b.md", "B")]).await;
+
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries[0]["original_content"], "# A\n\nThe full text.");
+    assert!(entries[1].get("original_content").is_none());
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use common::{serve, Response};
+use doctldr::cache::SummaryCache;
+use doctldr::config::Config;
+use doctldr::llm::{OllamaProvider, OpenAiProvider, ProviderOptions};
+use doctldr::testing::MockProvider;
+use doctldr::LlmSummarizer;
+
+/// Serves a fake chat completions API on a local port and returns its base
+/// URL and a count of requests. Batched requests are answered with one
+/// numbered summary per document, unless `split` is false, in which case
+/// the answer has no delimiters at all.
+fn serve_api(split: bool) -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let count = Arc::clone(&requests);
+
+    let base = serve(move |request| {
+        count.fetch_add(1, Ordering::SeqCst);
+        // Delimiters start a line; the instructions mention them mid-line
+        let documents = request.body.matches("\\n=== DOCUMENT ").count();
+        let text = if documents == 0 || !split {
+            "Single summary.".to_string()
+        } else {
+            (1..=documents).rev().map(|n| format!("=== SUMMARY {} ===\nSummary {}.\n", n, n)).collect()
+        };
+        Response::completion(&text)
+    });
+
+    (base, requests)
+}
+
+fn summarizer(base: String) -> LlmSummarizer {
+    cached_summarizer(base, None)
+}
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.api.max_retries = 0;
+    config.processing.batch_threshold_tokens = 100;
+    config.processing.chunk_size_tokens = Some(300);
+    config
+}
+
+fn cached_summarizer(base: String, cache: Option<SummaryCache>) -> LlmSummarizer {
+    let config = config();
+    let provider = OpenAiProvider::new("key".to_string(), base, "gpt-4".to_string(), ProviderOptions::from_config(&config).unwrap());
+    LlmSummarizer::new(Box::new(provider), &config, cache).unwrap()
+}
+
+#[tokio::test]
+async fn small_documents_share_one_request() {
+    let (base, requests) = serve_api(true);
+    let summarizer = summarizer(base);
+    let long = "word ".repeat(500);
+    let contents = ["First doc.", "Second doc.", long.as_str(), "Third doc."];
+
+    let documents: Vec<_> = contents.iter().map(|&content| (content, None)).collect();
+    let batches = summarizer.batches(&documents);
+    assert_eq!(batches, [vec![2], vec![0, 1, 3]]);
+
+    let batch: Vec<&str> = batches[1].iter().map(|&index| contents[index]).collect();
+    let summaries: Vec<_> = summarizer.summarize_batch(&batch, None).await.into_iter().map(Result::unwrap).collect();
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    let texts: Vec<&str> = summaries.iter().map(|summary| summary.text.as_str()).collect();
+    assert_eq!(texts, ["Summary 1.", "Summary 2.", "Summary 3."]);
+    assert_eq!(summaries[0].model, "gpt-4-0613");
+    assert_eq!(summaries.iter().map(|summary| summary.usage.output_tokens).sum::<usize>(), 12);
+}
+
+#[tokio::test]
+async fn unsplittable_answers_fall_back_to_single_requests() {
+    let (base, requests) = serve_api(false);
+    let summarizer = summarizer(base);
+
+    let summaries = summarizer.summarize_batch(&["First doc.", "Second doc."], None).await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    assert!(summaries.iter().all(|summary| summary.as_ref().unwrap().text == "Single summary."));
+}
+
+#[tokio::test]
+async fn batches_reuse_summaries_cached_one_at_a_time() {
+    let (base, requests) = serve_api(true);
+    let dir = tempfile::tempdir().unwrap();
+    let cache = SummaryCache::new(dir.path().to_path_buf(), "gpt-4".to_string());
+    let long = "word ".repeat(500);
+    let contents = ["Short doc.", long.as_str()];
+
+    let single = cached_summarizer(base.clone(), Some(cache.clone()));
+    for content in contents {
+        single.summarize(content, None).await.unwrap();
+    }
+    let sent = requests.load(Ordering::SeqCst);
+
+    let batched = cached_summarizer(base, Some(cache));
+    let summaries = batched.summarize_batch(&contents, None).await;
+
+    assert!(summaries.iter().all(Result::is_ok));
+    assert_eq!(requests.load(Ordering::SeqCst), sent);
+    assert_eq!(batched.cache_stats().misses, 0);
+}
+
+#[tokio::test]
+async fn cache_hits_in_a_batch_get_the_self_check() {
+    let (base, requests) = serve_api(true);
+    let dir = tempfile::tempdir().unwrap();
+    let cache = SummaryCache::new(dir.path().to_path_buf(), "gpt-4".to_string());
+    let mut config = config();
+    let refusing = LlmSummarizer::new(Box::new(MockProvider::canned("I'm sorry, I can't help with that.")), &config, Some(cache.clone())).unwrap();
+    refusing.summarize("Short doc.", None).await.unwrap();
+
+    config.api.max_retries = 1;
+    config.default.self_check = true;
+    let provider = OpenAiProvider::new("key".to_string(), base, "gpt-4".to_string(), ProviderOptions::from_config(&config).unwrap());
+    let batched = LlmSummarizer::new(Box::new(provider), &config, Some(cache)).unwrap();
+    let summaries = batched.summarize_batch(&["Short doc.", "Other doc."], None).await;
+
+    let hit = summaries[0].as_ref().unwrap();
+    assert_eq!(hit.text, "Single summary.");
+    assert!(hit.retried);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn providers_without_batching_get_the_full_budget_per_document() {
+    let budgets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&budgets);
+    let base = serve(move |request| {
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        seen.lock().unwrap().push(body["options"]["num_predict"].as_u64().unwrap());
+        Response::json("200 OK", serde_json::json!({ "response": "A local summary." }))
+    });
+    let config = config();
+    let provider = OllamaProvider::new("llama3".to_string(), base, ProviderOptions::from_config(&config).unwrap());
+    let summarizer = LlmSummarizer::new(Box::new(provider), &config, None).unwrap();
+    assert!(!summarizer.supports_batch());
+
+    summarizer.summarize("First doc.", None).await.unwrap();
+    let summaries = summarizer.summarize_batch(&["First doc.", "Second doc."], None).await;
+
+    assert!(summaries.iter().all(Result::is_ok));
+    let budgets = budgets.lock().unwrap();
+    assert_eq!(budgets.len(), 3);
+    assert!(budgets.iter().all(|&budget| budget == budgets[0]));
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use doctldr::cache::SummaryCache;
+use doctldr::config::Config;
+use doctldr::processing::DocumentFormat;
+use doctldr::testing::MockProvider;
+use doctldr::LlmSummarizer;
+
+/// Summarizes `content` as `format` with `config` against the cache in
+/// `dir`. On a cache miss the provider answers `fresh`.
+async fn summarize(dir: &Path, config: &Config, content: &str, format: Option<DocumentFormat>, fresh: &str) -> String {
+    let cache = SummaryCache::new(dir.to_path_buf(), config.default.model.clone());
+    let summarizer = LlmSummarizer::new(Box::new(MockProvider::canned(fresh)), config, Some(cache)).unwrap();
+    summarizer.summarize(content, format).await.unwrap().text
+}
+
+#[tokio::test]
+async fn system_prompts_key_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let markdown = Some(DocumentFormat::Markdown);
+    let html = Some(DocumentFormat::Html);
+    let mut config = Config::default();
+    config.api.system_prompts.insert("html".to_string(), "Summarize this web page.".to_string());
+
+    assert_eq!(summarize(dir.path(), &config, "Same text.", markdown, "First.").await, "First.");
+    assert_eq!(summarize(dir.path(), &config, "Same text.", markdown, "Second.").await, "First.");
+    // The same content as another format gets that format's prompt
+    assert_eq!(summarize(dir.path(), &config, "Same text.", html, "Third.").await, "Third.");
+
+    config.api.system_prompts.insert("html".to_string(), "Summarize this page in one line.".to_string());
+    assert_eq!(summarize(dir.path(), &config, "Same text.", html, "Fourth.").await, "Fourth.");
+}
+
+#[tokio::test]
+async fn prompt_template_keys_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.api.prompt_template = Some("Summarize for operators:\n\n{content}".to_string());
+
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "First.").await, "First.");
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "Second.").await, "First.");
+
+    config.api.prompt_template = Some("Summarize for new users:\n\n{content}".to_string());
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "Third.").await, "Third.");
+}
+
+#[tokio::test]
+async fn editing_the_prompt_file_misses_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let prompt_file = dir.path().join("prompt.txt");
+    std::fs::write(&prompt_file, "Summarize briefly:\n\n{content}").unwrap();
+    let cache_dir = dir.path().join("cache");
+    let mut config = Config::default();
+    config.api.prompt_file = Some(prompt_file.clone());
+
+    assert_eq!(summarize(&cache_dir, &config, "Same text.", None, "First.").await, "First.");
+    assert_eq!(summarize(&cache_dir, &config, "Same text.", None, "Second.").await, "First.");
+
+    std::fs::write(&prompt_file, "Summarize as release notes:\n\n{content}").unwrap();
+    assert_eq!(summarize(&cache_dir, &config, "Same text.", None, "Third.").await, "Third.");
+}
+
+#[tokio::test]
+async fn temperature_keys_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.default.temperature = 0.7;
+
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "First.").await, "First.");
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "Second.").await, "First.");
+
+    // A deterministic run must not reuse summaries sampled at 0.7
+    config.make_deterministic();
+    assert_eq!(summarize(dir.path(), &config, "Same text.", None, "Third.").await, "Third.");
+}
@@ -0,0 +1,22 @@
+use doctldr::cache::SummaryCache;
+use doctldr::config::Config;
+use doctldr::testing::MockProvider;
+use doctldr::LlmSummarizer;
+
+#[tokio::test]
+async fn cache_hits_and_misses_are_counted() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::default();
+    let cache = SummaryCache::new(dir.path().to_path_buf(), config.default.model.clone());
+    let summarizer = LlmSummarizer::new(Box::new(MockProvider::canned("Cached summary.")), &config, Some(cache)).unwrap();
+
+    summarizer.summarize("First document.", None).await.unwrap();
+    summarizer.summarize("Second document.", None).await.unwrap();
+    summarizer.summarize("First document.", None).await.unwrap();
+
+    let stats = summarizer.cache_stats();
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 2);
+    assert_eq!(stats.saved.output_tokens, summarizer.count_tokens("Cached summary."));
+    assert!(stats.saved.input_tokens > summarizer.count_tokens("First document."));
+}
@@ -0,0 +1,122 @@
+//! Fixtures shared by the integration tests. Each test crate uses only
+//! some of them.
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+
+use doctldr::output::{Summary, SummaryMetadata};
+
+/// A summary of `text` for a 100-byte original at `path`.
+pub fn summary(path: &str, text: &str) -> Summary {
+    Summary {
+        original_path: path.to_string(),
+        relative_path: None,
+        summary: text.to_string(),
+        metadata: SummaryMetadata {
+            original_size: 100,
+            summary_size: text.len() as u64,
+            compression_ratio: text.len() as f64 / 100.0,
+            ..Default::default()
+        },
+        original_content: None,
+    }
+}
+
+/// A request received by [`serve`].
+pub struct Request {
+    pub path: String,
+    pub body: String,
+}
+
+/// The answer [`serve`] writes back for one request.
+pub struct Response {
+    /// Status line after the HTTP version, such as `200 OK`.
+    pub status: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: &str, content_type: &str, body: impl Into<String>) -> Self {
+        Self {
+            status: status.to_string(),
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn json(status: &str, body: serde_json::Value) -> Self {
+        Self::new(status, "application/json", body.to_string())
+    }
+
+    /// A chat completion answering with `text`.
+    pub fn completion(text: &str) -> Self {
+        Self::json(
+            "200 OK",
+            serde_json::json!({
+                "model": "gpt-4-0613",
+                "choices": [{ "message": { "content": text } }],
+                "usage": { "prompt_tokens": 90, "completion_tokens": 12 },
+            }),
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Serves HTTP on a local port, answering every request with `respond`, and
+/// returns its base URL. Each connection gets its own thread, so a slow
+/// answer doesn't hold up the others.
+pub fn serve(respond: impl Fn(Request) -> Response + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let respond = Arc::new(respond);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let respond = Arc::clone(&respond);
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let response = respond(Request { path, body });
+                let headers: String = response.headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.status,
+                    headers,
+                    response.body.len(),
+                    response.body
+                );
+            });
+        }
+    });
+
+    base
+}
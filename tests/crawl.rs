@@ -0,0 +1,60 @@
+mod common;
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::crawl::{crawl, CrawlOptions};
+use doctldr::DocumentProcessor;
+
+/// Serves a small site on a local port and returns its base URL.
+fn serve_site() -> String {
+    let external = "http://example.invalid/elsewhere";
+
+    serve(move |request| match request.path.as_str() {
+        "/robots.txt" => Response::new("200 OK", "text/plain", "User-agent: *\nDisallow: /private\n"),
+        "/" => Response::new(
+            "200 OK",
+            "text/html",
+            format!(
+                r#"<a href="/guide">Guide</a> <a href="/private/keys">Keys</a>
+                   <a href="/logo.png">Logo</a> <a href="{}">Elsewhere</a> <a href="/guide#setup">Setup</a>"#,
+                external
+            ),
+        ),
+        "/guide" => Response::new("200 OK", "text/html", r#"<p>Guide</p><a href="/guide/deep">Deeper</a>"#),
+        "/guide/deep" => Response::new("200 OK", "text/html", "<p>Deep page</p>"),
+        _ => Response::new("404 Not Found", "text/plain", ""),
+    })
+}
+
+#[tokio::test]
+async fn crawls_same_host_links_within_limits() {
+    let base = serve_site();
+    let processor = DocumentProcessor::new(&Config::default().processing, false);
+    let client = reqwest::Client::new();
+    let options = CrawlOptions {
+        max_pages: 10,
+        max_depth: 1,
+        same_host: true,
+    };
+
+    let documents = crawl(&processor, &client, &format!("{}/", base), "doctldr/test", &options).await.unwrap();
+    let paths: Vec<String> = documents.iter().map(|d| d.path.to_string_lossy().into_owned()).collect();
+
+    // robots.txt blocks /private, assets and other hosts are skipped, the
+    // fragment link is the same page, and /guide/deep is past the depth limit
+    assert_eq!(paths, [format!("{}/", base), format!("{}/guide", base)]);
+}
+
+#[tokio::test]
+async fn stops_at_max_pages() {
+    let base = serve_site();
+    let processor = DocumentProcessor::new(&Config::default().processing, false);
+    let client = reqwest::Client::new();
+    let options = CrawlOptions {
+        max_pages: 1,
+        ..Default::default()
+    };
+
+    let documents = crawl(&processor, &client, &base, "doctldr/test", &options).await.unwrap();
+    assert_eq!(documents.len(), 1);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::summary;
+use doctldr::output::{CsvFormatter, OutputFormatter};
+
+#[test]
+fn csv_round_trips_through_a_reader() {
+    let mut summaries = vec![
+        summary("docs/api.md", "Covers the \"v2\" API.\n\n- auth, tokens\n- rate limits"),
+        summary("docs/plain.md", "One line."),
+    ];
+    summaries[0].metadata.original_size = 2000;
+    summaries[0].metadata.compression_ratio = 0.25;
+    let csv = CsvFormatter { include_metadata: true }.format(&summaries).unwrap();
+
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["path", "original_size", "summary_size", "compression_ratio", "summary"]
+    );
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(&rows[0][0], "docs/api.md");
+    assert_eq!(&rows[0][1], "2000");
+    assert_eq!(&rows[0][3], "0.25");
+    assert_eq!(&rows[0][4], "Covers the \"v2\" API.\n\n- auth, tokens\n- rate limits");
+    assert_eq!(&rows[1][4], "One line.");
+
+    let parsed = CsvFormatter { include_metadata: true }.parse(&csv).unwrap();
+    assert_eq!(parsed[0].summary, summaries[0].summary);
+    assert_eq!(parsed[0].metadata.original_size, 2000);
+}
+
+#[test]
+fn csv_without_metadata_has_path_and_summary_only() {
+    let csv = CsvFormatter { include_metadata: false }.format(&[summary("a.md", "Text.")]).unwrap();
+    assert_eq!(csv, "path,summary\na.md,Text.\n");
+}
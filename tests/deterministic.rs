@@ -0,0 +1,55 @@
+mod common;
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::{run, RunOptions};
+
+/// Serves a fake chat completions API that answers with the document's
+/// `topic-...` marker.
+fn serve_api() -> String {
+    serve(|request| {
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["temperature"], 0.0);
+
+        let topic: String =
+            request.body.split("topic-").nth(1).unwrap_or("").chars().take_while(char::is_ascii_alphabetic).collect();
+        Response::completion(&format!("Covers {}.", topic))
+    })
+}
+
+#[tokio::test]
+async fn deterministic_runs_write_identical_output() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::create_dir(docs.path().join("guides")).unwrap();
+    for (path, topic) in [("zeta.md", "zeta"), ("guides/install.md", "install"), ("alpha.md", "alpha")] {
+        std::fs::write(docs.path().join(path), format!("# Doc\n\ntopic-{} text.\n", topic)).unwrap();
+    }
+    let out = tempfile::tempdir().unwrap();
+
+    std::env::set_var("DOCTLDR_DETERMINISTIC_TEST_KEY", "key");
+    let mut config = Config::default();
+    config.api.base_url = serve_api();
+    config.api.key_env = "DOCTLDR_DETERMINISTIC_TEST_KEY".to_string();
+    config.api.max_retries = 0;
+    config.default.format = "json".to_string();
+    config.processing.llm_concurrency = 8;
+    config.make_deterministic();
+    assert_eq!(config.default.temperature, 0.0);
+    assert_eq!((config.processing.read_concurrency, config.processing.llm_concurrency), (1, 1));
+
+    let mut written = Vec::new();
+    for name in ["first.json", "second.json"] {
+        let options = RunOptions {
+            output: Some(out.path().join(name)),
+            no_cache: true,
+            ..Default::default()
+        };
+        run(&config, &[docs.path().to_path_buf()], &options).await.unwrap();
+        written.push(std::fs::read(out.path().join(name)).unwrap());
+    }
+
+    assert_eq!(written[0], written[1]);
+    let summaries: Vec<serde_json::Value> = serde_json::from_slice(&written[0]).unwrap();
+    let texts: Vec<&str> = summaries.iter().map(|summary| summary["summary"].as_str().unwrap()).collect();
+    assert_eq!(texts, ["Covers alpha.", "Covers install.", "Covers zeta."]);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::path::Path;
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::output::read_summaries;
+use doctldr::{run, RunOptions};
+
+/// Serves a fake chat completions API that answers every request with the
+/// same summary.
+fn serve_api() -> String {
+    serve(|_| Response::completion("A summary."))
+}
+
+async fn summarized_paths(output: &Path, docs: &Path) -> Vec<String> {
+    let mut paths: Vec<String> = read_summaries(output)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|summary| summary.original_path.replace(&format!("{}/", docs.display()), ""))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[tokio::test]
+async fn diff_summarizes_only_new_and_changed_files() {
+    let docs = tempfile::tempdir().unwrap();
+    for name in ["kept", "edited", "deleted"] {
+        std::fs::write(docs.path().join(format!("{}.md", name)), format!("# {}\n\nOriginal text.\n", name)).unwrap();
+    }
+    let out = tempfile::tempdir().unwrap();
+    let previous = out.path().join("previous.json");
+    let changes = out.path().join("changes.json");
+
+    std::env::set_var("DOCTLDR_DIFF_TEST_KEY", "key");
+    let mut config = Config::default();
+    config.api.base_url = serve_api();
+    config.api.key_env = "DOCTLDR_DIFF_TEST_KEY".to_string();
+    config.api.max_retries = 0;
+    config.default.min_tokens = 0;
+    config.output.default_format = "json".to_string();
+    let inputs = [docs.path().to_path_buf()];
+
+    let first = RunOptions {
+        output: Some(previous.clone()),
+        no_cache: true,
+        ..Default::default()
+    };
+    run(&config, &inputs, &first).await.unwrap();
+    assert_eq!(summarized_paths(&previous, docs.path()).await, ["deleted.md", "edited.md", "kept.md"]);
+
+    std::fs::write(docs.path().join("edited.md"), "# edited\n\nRewritten text.\n").unwrap();
+    std::fs::remove_file(docs.path().join("deleted.md")).unwrap();
+    std::fs::write(docs.path().join("added.md"), "# added\n\nNew text.\n").unwrap();
+
+    let second = RunOptions {
+        output: Some(changes.clone()),
+        no_cache: true,
+        diff: Some(previous),
+        ..Default::default()
+    };
+    let outcome = run(&config, &inputs, &second).await.unwrap();
+    assert_eq!(outcome.files_processed, 2);
+    assert_eq!(summarized_paths(&changes, docs.path()).await, ["added.md", "edited.md"]);
+}
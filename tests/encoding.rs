@@ -0,0 +1,85 @@
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+const BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+fn write(dir: &std::path::Path, name: &str, bytes: &[u8]) {
+    std::fs::write(dir.join(name), bytes).unwrap();
+}
+
+#[tokio::test]
+async fn valid_utf8_is_not_lossy() {
+    let docs = tempfile::tempdir().unwrap();
+    write(docs.path(), "ok.txt", "Grüße aus Köln".as_bytes());
+
+    let config = Config::default();
+    let documents = DocumentProcessor::new(&config.processing, false).process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].content, "Grüße aus Köln");
+    assert_eq!(documents[0].metadata.encoding, "UTF-8");
+    assert!(!documents[0].metadata.lossy);
+}
+
+#[tokio::test]
+async fn legacy_encodings_are_not_lossy() {
+    let docs = tempfile::tempdir().unwrap();
+    write(docs.path(), "latin1.txt", b"Gr\xfc\xdfe");
+
+    let config = Config::default();
+    let documents = DocumentProcessor::new(&config.processing, false).process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents[0].content, "Grüße");
+    assert_eq!(documents[0].metadata.encoding, "windows-1252");
+    assert!(!documents[0].metadata.lossy);
+}
+
+#[tokio::test]
+async fn invalid_bytes_are_replaced_and_flagged() {
+    let docs = tempfile::tempdir().unwrap();
+    write(docs.path(), "broken.txt", &[BOM, b"caf\xc3 ok"].concat());
+
+    let config = Config::default();
+    let documents = DocumentProcessor::new(&config.processing, false).process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].content, "caf\u{FFFD} ok");
+    assert!(documents[0].metadata.lossy);
+}
+
+#[tokio::test]
+async fn strict_encoding_skips_lossy_files() {
+    let docs = tempfile::tempdir().unwrap();
+    write(docs.path(), "broken.txt", &[BOM, b"caf\xc3 ok"].concat());
+    write(docs.path(), "ok.txt", b"fine");
+
+    let mut config = Config::default();
+    config.processing.strict_encoding = true;
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let documents = processor.process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert!(documents[0].path.ends_with("ok.txt"));
+    assert_eq!(processor.failed_count(), 1);
+}
+
+#[tokio::test]
+async fn detects_shift_jis() {
+    let docs = tempfile::tempdir().unwrap();
+    // "日本語のドキュメントを要約するためのツールです。設定ファイルで動作を変更できます。"
+    write(
+        docs.path(),
+        "sjis.txt",
+        b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x68\x83\x4c\x83\x85\x83\x81\x83\x93\x83\x67\x82\xf0\x97\x76\x96\xf1\
+\x82\xb7\x82\xe9\x82\xbd\x82\xdf\x82\xcc\x83\x63\x81\x5b\x83\x8b\x82\xc5\x82\xb7\x81\x42\x90\xdd\x92\xe8\
+\x83\x74\x83\x40\x83\x43\x83\x8b\x82\xc5\x93\xae\x8d\xec\x82\xf0\x95\xcf\x8d\x58\x82\xc5\x82\xab\x82\xdc\
+\x82\xb7\x81\x42",
+    );
+
+    let config = Config::default();
+    let documents = DocumentProcessor::new(&config.processing, false).process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents[0].content, "日本語のドキュメントを要約するためのツールです。設定ファイルで動作を変更できます。");
+    assert_eq!(documents[0].metadata.encoding, "Shift_JIS");
+    assert!(!documents[0].metadata.lossy);
+}
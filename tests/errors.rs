@@ -0,0 +1,65 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::llm::{provider_from_config, ApiError};
+use doctldr::{DocTldrError, LlmSummarizer};
+
+/// Serves a fake API answering every request with `status`, the extra
+/// `headers` and `body`.
+fn serve_error(status: &'static str, headers: &'static [(&'static str, &'static str)], body: serde_json::Value) -> String {
+    serve(move |_| {
+        headers.iter().fold(Response::json(status, body.clone()), |response, (name, value)| response.with_header(name, value))
+    })
+}
+
+async fn summarize_against(base_url: String) -> DocTldrError {
+    std::env::set_var("DOCTLDR_ERRORS_TEST_KEY", "key");
+    let mut config = Config::default();
+    config.api.base_url = base_url;
+    config.api.key_env = "DOCTLDR_ERRORS_TEST_KEY".to_string();
+    config.api.max_retries = 0;
+    let summarizer = LlmSummarizer::new(provider_from_config(&config).unwrap(), &config, None).unwrap();
+    summarizer.summarize("A short guide.", None).await.unwrap_err()
+}
+
+#[tokio::test]
+async fn rate_limits_carry_retry_after() {
+    let base = serve_error(
+        "429 Too Many Requests",
+        &[("Retry-After", "7")],
+        serde_json::json!({ "error": { "message": "Rate limit reached", "type": "requests" } }),
+    );
+    match summarize_against(base).await {
+        DocTldrError::RateLimited { retry_after, source } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(7)));
+            assert_eq!(source.to_string(), "API request failed with status 429 Too Many Requests: Rate limit reached");
+        }
+        other => panic!("expected RateLimited, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn context_overflows_are_told_apart_from_other_rejections() {
+    let base = serve_error(
+        "400 Bad Request",
+        &[],
+        serde_json::json!({ "error": {
+            "message": "This model's maximum context length is 8192 tokens.",
+            "code": "context_length_exceeded",
+        } }),
+    );
+    assert!(matches!(summarize_against(base).await, DocTldrError::ContextExceeded(_)));
+
+    let base = serve_error("401 Unauthorized", &[], serde_json::json!({ "error": { "message": "Incorrect API key" } }));
+    match summarize_against(base).await {
+        DocTldrError::Provider(error) => {
+            let api = error.downcast_ref::<ApiError>().unwrap();
+            assert_eq!(api.status.as_u16(), 401);
+            assert_eq!(api.message, "Incorrect API key");
+        }
+        other => panic!("expected Provider, got {:?}", other),
+    }
+}
@@ -0,0 +1,22 @@
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+#[tokio::test]
+async fn output_inside_input_is_not_processed() {
+    let docs = tempfile::tempdir().unwrap();
+    let split = docs.path().join("summaries");
+    std::fs::create_dir(&split).unwrap();
+    std::fs::write(docs.path().join("guide.md"), "# Guide\n").unwrap();
+    std::fs::write(docs.path().join("summary.md"), "# Summary of guide.md\n").unwrap();
+    std::fs::write(split.join("guide.md.summary.md"), "# Summary of guide.md\n").unwrap();
+
+    let outputs = vec![
+        docs.path().join("summary.md").canonicalize().unwrap(),
+        split.canonicalize().unwrap(),
+    ];
+    let processor = DocumentProcessor::new(&Config::default().processing, false).with_excluded_outputs(outputs);
+    let documents = processor.process_directory(docs.path()).await.unwrap();
+
+    let names: Vec<_> = documents.iter().map(|d| d.path.file_name().unwrap().to_owned()).collect();
+    assert_eq!(names, ["guide.md"]);
+}
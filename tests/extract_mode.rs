@@ -0,0 +1,48 @@
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+const GUIDE: &str = "Intro text for the guide. It goes on.
+
+# Install
+
+Run `cargo install doctldr` first. Then check v1.2 is on the PATH.
+
+```sh
+cargo install doctldr
+```
+
+More detail nobody needs.
+
+## Configure
+
+- a tight list
+- that is dropped
+
+Edit the config file! It lives in the config dir.
+
+## Empty
+";
+
+#[tokio::test]
+async fn headings_mode_keeps_headings_and_first_sentences() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("guide.md"), GUIDE).unwrap();
+
+    let mut config = Config::default();
+    config.processing.extract_mode = "headings".to_string();
+    config.validate().unwrap();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let document = processor.process_directory(docs.path()).await.unwrap().remove(0);
+
+    assert_eq!(
+        document.content,
+        "Intro text for the guide.\n\n# Install\n\nRun `cargo install doctldr` first.\n\n## Configure\n\nEdit the config file!\n\n## Empty"
+    );
+}
+
+#[test]
+fn unknown_extract_modes_are_rejected() {
+    let mut config = Config::default();
+    config.processing.extract_mode = "outline".to_string();
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,30 @@
+use doctldr::config::Config;
+use doctldr::processing::DocumentFormat;
+use doctldr::DocumentProcessor;
+
+#[tokio::test]
+async fn overridden_txt_is_processed_as_markdown() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("CHANGELOG.txt"), "---\ntitle: Changes\n---\n# 1.0\n\n- **First** release\n").unwrap();
+    std::fs::write(docs.path().join("notes.txt"), "# Not a heading here\n").unwrap();
+
+    let mut config = Config::default();
+    config.processing.format_overrides.insert("CHANGELOG*".to_string(), "markdown".to_string());
+    config.validate().unwrap();
+    let processor = DocumentProcessor::new(&config.processing, false);
+
+    let mut documents = processor.process_directory(docs.path()).await.unwrap();
+    documents.sort_by(|a, b| a.path.cmp(&b.path));
+
+    assert_eq!(documents[0].format, DocumentFormat::Markdown);
+    assert_eq!(documents[0].metadata.title().as_deref(), Some("Changes"));
+    assert!(!documents[0].content.contains("**"));
+    assert_eq!(documents[1].format, DocumentFormat::PlainText);
+}
+
+#[test]
+fn unknown_override_format_is_rejected() {
+    let mut config = Config::default();
+    config.processing.format_overrides.insert("*.log".to_string(), "logfile".to_string());
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,43 @@
+use doctldr::config::Config;
+use doctldr::testing::MockProvider;
+use doctldr::{DocumentProcessor, LlmSummarizer, Summary};
+
+#[tokio::test]
+async fn front_matter_is_stripped_and_reported() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(
+        docs.path().join("guide.md"),
+        "---\ntitle: Install guide\ntags: [setup, cargo]\n---\n# Install\n\nRun cargo install.\n",
+    )
+    .unwrap();
+
+    let config = Config::default();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let summarizer = LlmSummarizer::new(Box::new(MockProvider::echo("", 200)), &config, None).unwrap();
+    let document = processor.process_directory(docs.path()).await.unwrap().remove(0);
+
+    assert!(document.metadata.front_matter.is_some());
+    assert!(!document.content.contains("title:"));
+
+    let summary = Summary::new(&document, summarizer.summarize(&document.content, Some(document.format)).await.unwrap());
+    assert_eq!(summary.metadata.title.as_deref(), Some("Install guide"));
+    assert_eq!(summary.metadata.tags, ["setup", "cargo"]);
+}
+
+#[tokio::test]
+async fn documents_without_front_matter_are_unchanged() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("rule.md"), "---\n\nText after a rule.\n").unwrap();
+    std::fs::write(docs.path().join("plain.md"), "# Plain\n\nNo front matter.\n").unwrap();
+
+    let config = Config::default();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let documents = processor.process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents.len(), 2);
+    for document in &documents {
+        assert!(document.metadata.front_matter.is_none());
+        assert!(document.metadata.title().is_none());
+        assert!(document.metadata.tags().is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+use std::path::Path;
+use std::process::Command;
+
+use doctldr::processing::clone_git_repo;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=doctldr", "-c", "user.email=doctldr@example.com", "-C"])
+        .arg(dir)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[tokio::test]
+async fn clones_the_requested_revision() {
+    let repo = tempfile::tempdir().unwrap();
+    git(repo.path(), &["init", "--quiet"]);
+    std::fs::write(repo.path().join("README.md"), "# First\n").unwrap();
+    git(repo.path(), &["add", "."]);
+    git(repo.path(), &["commit", "--quiet", "-m", "first"]);
+    git(repo.path(), &["tag", "v1"]);
+    std::fs::write(repo.path().join("README.md"), "# Second\n").unwrap();
+    git(repo.path(), &["commit", "--quiet", "-am", "second"]);
+
+    let url = repo.path().to_str().unwrap();
+    let latest = clone_git_repo(url, None).await.unwrap();
+    assert_eq!(std::fs::read_to_string(latest.path().join("README.md")).unwrap(), "# Second\n");
+
+    let tagged = clone_git_repo(url, Some("v1")).await.unwrap();
+    let checkout = tagged.path().to_owned();
+    assert_eq!(std::fs::read_to_string(checkout.join("README.md")).unwrap(), "# First\n");
+
+    drop(tagged);
+    assert!(!checkout.exists());
+}
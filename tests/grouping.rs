@@ -0,0 +1,59 @@
+use doctldr::config::Config;
+use doctldr::llm::{GeneratedSummary, TokenUsage};
+use doctldr::output::FormatOptions;
+use doctldr::{DocumentProcessor, OutputWriter, Summary};
+
+#[tokio::test]
+async fn markdown_groups_summaries_by_top_level_directory() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(docs.path().join("api/v2")).unwrap();
+    std::fs::create_dir_all(docs.path().join("guides")).unwrap();
+    for (path, text) in [
+        ("api/auth.md", "Auth."),
+        ("api/v2/users.md", "Users."),
+        ("guides/start.md", "Start."),
+        ("README.md", "Readme."),
+    ] {
+        std::fs::write(docs.path().join(path), text).unwrap();
+    }
+
+    let config = Config::default();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let mut documents = processor.process_directory(docs.path()).await.unwrap();
+    documents.sort_by(|a, b| a.path.cmp(&b.path));
+    let summaries: Vec<Summary> = documents
+        .iter()
+        .map(|document| {
+            let generated = GeneratedSummary {
+                text: document.content.clone(),
+                chunks: 1,
+                model: "mock".to_string(),
+                usage: TokenUsage::default(),
+                retried: false,
+                max_tokens: 2048,
+            };
+            Summary::new(document, generated)
+        })
+        .collect();
+
+    let relative: Vec<_> = summaries.iter().map(|summary| summary.relative_path.as_deref().unwrap()).collect();
+    assert_eq!(relative, ["README.md", "api/auth.md", "api/v2/users.md", "guides/start.md"]);
+
+    let options = FormatOptions {
+        group_by_directory: true,
+        heading_template: Some("### {number}. {path}".to_string()),
+        separator: Some("\n\n".to_string()),
+        include_metadata: false,
+        ..Default::default()
+    };
+    let out = docs.path().join("summary.md");
+    OutputWriter::with_options("md", &options).unwrap().write(summaries, Some(&out)).await.unwrap();
+    let written = std::fs::read_to_string(&out).unwrap().replace(&format!("{}/", docs.path().display()), "");
+
+    assert_eq!(
+        written,
+        "### 1. README.md\n\nReadme.\n\n\
+         ## api/\n\n### 2. api/auth.md\n\nAuth.\n\n### 3. api/v2/users.md\n\nUsers.\n\n\
+         ## guides/\n\n### 4. guides/start.md\n\nStart.\n\n"
+    );
+}
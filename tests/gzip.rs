@@ -0,0 +1,47 @@
+use std::io::Write;
+
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+const MARKDOWN: &str = "# Setup\n\nRun `cargo build` and see the [guide](guide.md).\n\n- one\n- two\n";
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn gzipped_markdown_matches_original() {
+    let plain = tempfile::tempdir().unwrap();
+    let compressed = tempfile::tempdir().unwrap();
+    std::fs::write(plain.path().join("setup.md"), MARKDOWN).unwrap();
+    std::fs::write(compressed.path().join("setup.md.gz"), gzip(MARKDOWN.as_bytes())).unwrap();
+
+    let config = Config::default();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let original = processor.process_directory(plain.path()).await.unwrap();
+    let decompressed = processor.process_directory(compressed.path()).await.unwrap();
+
+    assert_eq!(decompressed.len(), 1);
+    assert!(decompressed[0].path.ends_with("setup.md.gz"));
+    assert_eq!(decompressed[0].content, original[0].content);
+    assert_eq!(decompressed[0].format, original[0].format);
+}
+
+#[tokio::test]
+async fn corrupt_gzip_is_skipped() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("broken.md.gz"), b"\x1f\x8b\x08\x00not gzip").unwrap();
+    std::fs::write(docs.path().join("ok.md"), MARKDOWN).unwrap();
+
+    let config = Config::default();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let documents = processor.process_directory(docs.path()).await.unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert!(documents[0].path.ends_with("ok.md"));
+    assert_eq!(processor.failed_count(), 1);
+}
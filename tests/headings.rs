@@ -0,0 +1,37 @@
+mod common;
+
+use common::summary;
+use doctldr::output::FormatOptions;
+use doctldr::OutputWriter;
+
+async fn render(format: &str, options: &FormatOptions) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summaries");
+    let mut summaries = vec![summary("a.md", "A"), summary("b.md", "B")];
+    // An uncompressed summary gets no compression note to clutter the layout
+    for summary in &mut summaries {
+        summary.metadata.compression_ratio = 1.0;
+    }
+    OutputWriter::with_options(format, options).unwrap().write(summaries, Some(&out)).await.unwrap();
+    std::fs::read_to_string(&out).unwrap()
+}
+
+#[tokio::test]
+async fn defaults_keep_the_built_in_layout() {
+    let options = FormatOptions::default();
+
+    assert_eq!(render("md", &options).await, "# Summary of a.md\n\nA\n\n---\n\n# Summary of b.md\n\nB\n\n---\n\n");
+    assert_eq!(render("txt", &options).await, "=== a.md ===\n\nA\n\n=== b.md ===\n\nB\n\n");
+}
+
+#[tokio::test]
+async fn heading_template_and_separator_replace_the_layout() {
+    let options = FormatOptions {
+        heading_template: Some("## {path}".to_string()),
+        separator: Some("\n\n* * *\n\n".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(render("md", &options).await, "## a.md\n\nA\n\n* * *\n\n## b.md\n\nB\n\n* * *\n\n");
+    assert_eq!(render("txt", &options).await, "## a.md\n\nA\n\n* * *\n\n## b.md\n\nB\n\n* * *\n\n");
+}
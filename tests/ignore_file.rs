@@ -0,0 +1,28 @@
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+#[tokio::test]
+async fn doctldrignore_files_apply_to_their_directory_and_below() {
+    let docs = tempfile::tempdir().unwrap();
+    let root = docs.path();
+    std::fs::create_dir_all(root.join("drafts")).unwrap();
+    std::fs::create_dir_all(root.join("guide")).unwrap();
+    std::fs::write(root.join(".doctldrignore"), "drafts/\n*.wip.md\n").unwrap();
+    std::fs::write(root.join("guide/.doctldrignore"), "!keep.wip.md\nold.md\n").unwrap();
+    for path in ["index.md", "notes.wip.md", "drafts/idea.md", "guide/setup.md", "guide/keep.wip.md", "guide/old.md"] {
+        std::fs::write(root.join(path), "# Doc\n\nText.\n").unwrap();
+    }
+
+    let processor = DocumentProcessor::new(&Config::default().processing, false);
+    let mut paths: Vec<String> = processor
+        .process_directory(root)
+        .await
+        .unwrap()
+        .iter()
+        .map(|document| document.path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect();
+    paths.sort();
+
+    // The nested file re-includes what the top-level one ignores
+    assert_eq!(paths, ["guide/keep.wip.md", "guide/setup.md", "index.md"]);
+}
@@ -0,0 +1,40 @@
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+async fn normalized(text: &str, configure: impl FnOnce(&mut Config)) -> String {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("notes.txt"), text).unwrap();
+
+    let mut config = Config::default();
+    configure(&mut config);
+    config.validate().unwrap();
+    let processor = DocumentProcessor::new(&config.processing, false);
+    processor.process_directory(docs.path()).await.unwrap().remove(0).content
+}
+
+#[tokio::test]
+async fn blank_line_runs_collapse_to_one() {
+    let text = "First.  \n\n\n\n\t\nSecond.\t\n\nThird.\n";
+    let content = normalized(text, |config| {
+        config.processing.normalize.collapse_blank_lines = true;
+        config.processing.normalize.trim_trailing_whitespace = true;
+    })
+    .await;
+    assert_eq!(content, "First.\n\nSecond.\n\nThird.\n");
+
+    assert_eq!(normalized(text, |_| {}).await, text);
+}
+
+#[tokio::test]
+async fn urls_are_stripped_or_replaced() {
+    let text = "Docs at https://example.com/guide?page=2. Mirror (ftp://mirror.example.org/pub) too.\n";
+    let stripped = normalized(text, |config| config.processing.normalize.strip_urls = true).await;
+    assert_eq!(stripped, "Docs at . Mirror () too.\n");
+
+    let replaced = normalized(text, |config| {
+        config.processing.normalize.strip_urls = true;
+        config.processing.normalize.url_placeholder = Some("[link]".to_string());
+    })
+    .await;
+    assert_eq!(replaced, "Docs at [link]. Mirror ([link]) too.\n");
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::{run, RunOptions};
+
+/// Serves a fake chat completions API that echoes the document's marker
+/// (`doc-a`, `doc-b`, ...) as the summary. Documents later in the alphabet
+/// are answered sooner, so parallel summaries complete in reverse order.
+fn serve_api() -> String {
+    serve(|request| {
+        let letter = request.body.split("doc-").nth(1).and_then(|rest| rest.chars().next()).unwrap_or('z');
+        std::thread::sleep(Duration::from_millis(50 * (b'e' - letter as u8) as u64));
+        Response::completion(&format!("Summary of doc-{}.", letter))
+    })
+}
+
+#[tokio::test]
+async fn numbers_follow_sort_order_not_completion_order() {
+    let docs = tempfile::tempdir().unwrap();
+    for letter in ['a', 'b', 'c', 'd'] {
+        std::fs::write(docs.path().join(format!("{}.md", letter)), format!("# Doc\n\ndoc-{} text.\n", letter)).unwrap();
+    }
+    let out = tempfile::tempdir().unwrap();
+    let output = out.path().join("summary.md");
+
+    std::env::set_var("DOCTLDR_NUMBERING_TEST_KEY", "key");
+    let mut config = Config::default();
+    config.api.base_url = serve_api();
+    config.api.key_env = "DOCTLDR_NUMBERING_TEST_KEY".to_string();
+    config.api.max_retries = 0;
+    config.default.min_tokens = 0;
+    config.processing.llm_concurrency = 4;
+    config.output.include_metadata = false;
+    config.output.numbered = true;
+    let options = RunOptions {
+        output: Some(output.clone()),
+        no_cache: true,
+        ..Default::default()
+    };
+
+    let outcome = run(&config, &[docs.path().to_path_buf()], &options).await.unwrap();
+    assert_eq!(outcome.files_processed, 4);
+
+    let written = std::fs::read_to_string(&output).unwrap();
+    let headings: Vec<String> = written
+        .lines()
+        .filter(|line| line.starts_with("# "))
+        .map(|line| line.replace(&format!("{}/", docs.path().display()), ""))
+        .collect();
+    assert_eq!(
+        headings,
+        ["# 1. Summary of a.md", "# 2. Summary of b.md", "# 3. Summary of c.md", "# 4. Summary of d.md"]
+    );
+    assert!(written.find("Summary of doc-a.").unwrap() < written.find("Summary of doc-d.").unwrap());
+}
@@ -0,0 +1,23 @@
+use doctldr::cache::SummaryCache;
+use doctldr::config::Config;
+use doctldr::llm::NotCached;
+use doctldr::testing::MockProvider;
+use doctldr::{DocTldrError, LlmSummarizer};
+
+#[tokio::test]
+async fn offline_serves_cached_summaries_and_fails_misses() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::default();
+    let cache = SummaryCache::new(dir.path().to_path_buf(), config.default.model.clone());
+
+    let online = LlmSummarizer::new(Box::new(MockProvider::canned("Cached summary.")), &config, Some(cache.clone())).unwrap();
+    online.summarize("Warmed document.", None).await.unwrap();
+
+    let offline = LlmSummarizer::offline(&config, cache).unwrap();
+    let hit = offline.summarize("Warmed document.", None).await.unwrap();
+    assert_eq!(hit.text, "Cached summary.");
+    assert_eq!(hit.usage.input_tokens, 0);
+
+    let miss = offline.summarize("Never summarized.", None).await.unwrap_err();
+    assert!(matches!(miss, DocTldrError::Provider(e) if e.is::<NotCached>()));
+}
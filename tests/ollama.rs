@@ -0,0 +1,29 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{serve, Response};
+use doctldr::config::Config;
+use doctldr::llm::provider_from_config;
+use doctldr::LlmSummarizer;
+
+#[tokio::test]
+async fn ollama_is_reached_at_the_configured_url() {
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&paths);
+    let base = serve(move |request| {
+        seen.lock().unwrap().push(request.path);
+        Response::json("200 OK", serde_json::json!({ "model": "llama3", "response": "A local summary." }))
+    });
+
+    let mut config = Config::default();
+    config.api.provider = "ollama".to_string();
+    config.api.ollama_url = Some(base);
+    config.api.max_retries = 0;
+    config.default.model = "llama3".to_string();
+    let summarizer = LlmSummarizer::new(provider_from_config(&config).unwrap(), &config, None).unwrap();
+
+    let summary = summarizer.summarize("A short guide.", None).await.unwrap();
+    assert_eq!(summary.text, "A local summary.");
+    assert_eq!(*paths.lock().unwrap(), ["/api/generate"]);
+}
@@ -0,0 +1,78 @@
+use doctldr::config::Config;
+use doctldr::testing::MockProvider;
+use doctldr::{DocumentProcessor, LlmSummarizer, OutputWriter, Summary};
+
+async fn summarize_dir(provider: MockProvider, config: &Config, dir: &std::path::Path) -> anyhow::Result<Vec<Summary>> {
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let summarizer = LlmSummarizer::new(Box::new(provider), config, None)?;
+
+    let mut summaries = Vec::new();
+    for document in processor.process_directory(dir).await? {
+        let generated = summarizer.summarize(&document.content, Some(document.format)).await?;
+        summaries.push(Summary::new(&document, generated));
+    }
+    summaries.sort_by(|a, b| a.original_path.cmp(&b.original_path));
+    Ok(summaries)
+}
+
+#[tokio::test]
+async fn summarizes_a_directory_to_json() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("intro.md"), "# Intro\n\nInstall with cargo.\n").unwrap();
+    std::fs::write(docs.path().join("notes.txt"), "Plain notes.\n").unwrap();
+    std::fs::write(docs.path().join("ignored.rs"), "fn main() {}\n").unwrap();
+
+    let config = Config::default();
+    let summaries = summarize_dir(MockProvider::echo("TL;DR: ", 12), &config, docs.path()).await.unwrap();
+
+    assert_eq!(summaries.len(), 2);
+    assert!(summaries[0].original_path.ends_with("intro.md"));
+    assert_eq!(summaries[0].summary, "TL;DR: # Intro\n\nIns");
+    assert_eq!(summaries[1].summary, "TL;DR: Plain notes.");
+
+    let out = docs.path().join("summary.json");
+    OutputWriter::new("json").unwrap().write(summaries, Some(&out)).await.unwrap();
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert_eq!(json[1]["summary"], "TL;DR: Plain notes.");
+}
+
+#[tokio::test]
+async fn chunks_long_documents() {
+    let docs = tempfile::tempdir().unwrap();
+    let paragraphs: Vec<String> = (0..40).map(|i| format!("Paragraph {} explains one more detail.", i)).collect();
+    std::fs::write(docs.path().join("long.md"), paragraphs.join("\n\n")).unwrap();
+
+    let mut config = Config::default();
+    config.processing.chunk_size_tokens = Some(50);
+    config.processing.chunk_overlap_tokens = 0;
+    let summaries = summarize_dir(MockProvider::canned("short"), &config, docs.path()).await.unwrap();
+
+    assert_eq!(summaries.len(), 1);
+    assert!(summaries[0].metadata.chunks > 1);
+    assert_eq!(summaries[0].summary, "short");
+    assert_eq!(summaries[0].metadata.model, "mock");
+    // Estimated, since the mock reports no usage: every chunk plus the final pass
+    assert!(summaries[0].metadata.input_tokens > 50 * summaries[0].metadata.chunks);
+}
+
+#[tokio::test]
+async fn surfaces_provider_errors() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("intro.md"), "# Intro\n").unwrap();
+
+    let result = summarize_dir(MockProvider::failing("rate limited"), &Config::default(), docs.path()).await;
+    assert_eq!(result.unwrap_err().to_string(), "rate limited");
+}
+
+#[tokio::test]
+async fn caps_summary_length() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("intro.md"), "Install with cargo and run the binary.").unwrap();
+
+    let mut config = Config::default();
+    config.default.max_summary_chars = Some(20);
+    let summaries = summarize_dir(MockProvider::echo("", 100), &config, docs.path()).await.unwrap();
+
+    assert_eq!(summaries[0].summary, "Install with cargo");
+}
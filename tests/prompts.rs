@@ -0,0 +1,21 @@
+use doctldr::config::Config;
+use doctldr::llm::{OpenAiProvider, ProviderOptions};
+use doctldr::processing::DocumentFormat;
+use doctldr::LlmSummarizer;
+
+#[test]
+fn system_prompt_follows_document_format() {
+    let mut config = Config::default();
+    config.api.system_prompt = Some("Generic prompt.".to_string());
+    config.api.system_prompts.insert("html".to_string(), "HTML prompt.".to_string());
+    config.validate().unwrap();
+
+    let options = ProviderOptions::from_config(&config).unwrap();
+    let provider = OpenAiProvider::new("key".to_string(), config.api.base_url.clone(), "gpt-4".to_string(), options);
+    let summarizer = LlmSummarizer::new(Box::new(provider), &config, None).unwrap();
+
+    let system = |format| summarizer.build_prompts("Some text.", format).unwrap()[0][0].content.clone();
+    assert_eq!(system(Some(DocumentFormat::Html)), "HTML prompt.");
+    assert_eq!(system(Some(DocumentFormat::Markdown)), "Generic prompt.");
+    assert_eq!(system(None), "Generic prompt.");
+}
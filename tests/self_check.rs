@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use doctldr::config::Config;
+use doctldr::llm::{LlmProvider, SummaryResult};
+use doctldr::processing::DocumentFormat;
+use doctldr::LlmSummarizer;
+
+/// Refuses the first `refusals` requests, then summarizes.
+struct Reluctant {
+    refusals: usize,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl LlmProvider for Reluctant {
+    async fn summarize(&self, _content: &str, _format: Option<DocumentFormat>, _max_tokens: usize) -> Result<SummaryResult> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        let text = if call < self.refusals {
+            "I'm sorry, but I cannot summarize this document."
+        } else {
+            "The guide explains installation and configuration."
+        };
+        Ok(SummaryResult {
+            text: text.to_string(),
+            usage: None,
+            model: "mock".to_string(),
+        })
+    }
+}
+
+fn summarizer(refusals: usize, max_retries: u32, self_check: bool) -> (LlmSummarizer, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut config = Config::default();
+    config.default.self_check = self_check;
+    config.api.max_retries = max_retries;
+    let provider = Reluctant { refusals, calls: Arc::clone(&calls) };
+    (LlmSummarizer::new(Box::new(provider), &config, None).unwrap(), calls)
+}
+
+#[tokio::test]
+async fn refusals_are_requested_again() {
+    let (summarizer, calls) = summarizer(1, 3, true);
+    let summary = summarizer.summarize("A short guide.", None).await.unwrap();
+
+    assert_eq!(summary.text, "The guide explains installation and configuration.");
+    assert!(summary.retried);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn retries_stop_at_max_retries() {
+    let (summarizer, calls) = summarizer(10, 2, true);
+    let summary = summarizer.summarize("A short guide.", None).await.unwrap();
+
+    assert!(summary.text.starts_with("I'm sorry"));
+    assert!(summary.retried);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn self_check_is_off_by_default() {
+    let (summarizer, calls) = summarizer(1, 3, false);
+    let summary = summarizer.summarize("A short guide.", None).await.unwrap();
+
+    assert!(summary.text.starts_with("I'm sorry"));
+    assert!(!summary.retried);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,174 @@
+use doctldr::config::Config;
+use doctldr::processing::DocumentFormat;
+use doctldr::source::SourceLanguage;
+use doctldr::DocumentProcessor;
+
+const RUST: &str = r#"//! Parsing of widget files.
+
+use std::collections::HashMap;
+
+/// A parsed widget.
+#[derive(Debug, Clone)]
+pub struct Widget {
+    /// Display name.
+    pub name: String,
+    cache: HashMap<String, u32>,
+}
+
+/// Ways parsing can fail.
+pub enum ParseError {
+    /// The input was empty.
+    Empty,
+    Invalid { line: usize },
+}
+
+impl Widget {
+    /// Parses `input`, which must not be empty.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        if input.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let braces = "}}}";
+        Ok(Self { name: input.to_string(), cache: HashMap::new() })
+    }
+
+    fn helper(&self) -> char {
+        '{'
+    }
+}
+
+/// Helpers nobody outside sees.
+fn private_helper() {}
+
+pub trait Render {
+    /// Renders to a string.
+    fn render(&self) -> String;
+
+    fn width(&self) -> usize {
+        80
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses() {}
+}
+"#;
+
+const PYTHON: &str = r#"#!/usr/bin/env python3
+"""Parsing of widget files."""
+
+import os
+
+
+class Widget(Base):
+    """A parsed widget."""
+
+    name: str
+
+    def __init__(self, name):
+        self.name = name
+
+    @classmethod
+    def parse(cls, text: str,
+              strict: bool = False) -> "Widget":
+        """Parse ``text``.
+
+        Raises ValueError when it is empty.
+        """
+        if not text:
+            raise ValueError("""
+def not_a_function():
+""")
+        return cls(text)
+
+    def _validate(self):
+        """Private."""
+        return True
+
+
+def _helper():
+    pass
+
+
+async def load(path): return Widget.parse(open(path).read())
+"#;
+
+#[test]
+fn rust_keeps_public_docs_and_signatures() {
+    let extracted = SourceLanguage::Rust.extractor().extract(RUST).unwrap();
+
+    assert_eq!(
+        extracted,
+        r#"//! Parsing of widget files.
+
+/// A parsed widget.
+pub struct Widget {
+    /// Display name.
+    pub name: String,
+}
+
+/// Ways parsing can fail.
+pub enum ParseError {
+    /// The input was empty.
+    Empty,
+    Invalid { line: usize },
+}
+
+impl Widget {
+    /// Parses `input`, which must not be empty.
+    pub fn parse(input: &str) -> Result<Self, ParseError>;
+}
+
+pub trait Render {
+    /// Renders to a string.
+    fn render(&self) -> String;
+    fn width(&self) -> usize;
+}"#
+    );
+}
+
+#[test]
+fn python_keeps_docstrings_and_public_signatures() {
+    let extracted = SourceLanguage::Python.extractor().extract(PYTHON).unwrap();
+
+    assert_eq!(
+        extracted,
+        r#""""Parsing of widget files."""
+
+class Widget(Base):
+    """A parsed widget."""
+    name: str
+    def __init__(self, name):
+    @classmethod
+    def parse(cls, text: str,
+              strict: bool = False) -> "Widget":
+        """Parse ``text``.
+
+        Raises ValueError when it is empty.
+        """
+
+async def load(path):"#
+    );
+}
+
+#[tokio::test]
+async fn source_files_are_detected_by_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("lib.rs"), RUST).unwrap();
+    std::fs::write(dir.path().join("widget.py"), PYTHON).unwrap();
+
+    let mut config = Config::default();
+    config.processing.include_patterns = vec!["*.rs".to_string(), "*.py".to_string()];
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let mut documents = processor.process_directory(dir.path()).await.unwrap();
+    documents.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let formats: Vec<_> = documents.iter().map(|document| document.format).collect();
+    assert_eq!(
+        formats,
+        [DocumentFormat::SourceCode(SourceLanguage::Rust), DocumentFormat::SourceCode(SourceLanguage::Python)]
+    );
+    assert!(documents.iter().all(|document| !document.content.contains("return")));
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{serve, Response};
+use doctldr::cache::SummaryCache;
+use doctldr::config::Config;
+use doctldr::llm::{GeneratedSummary, IncompleteStream, OpenAiProvider, ProviderOptions};
+use doctldr::testing::MockProvider;
+use doctldr::LlmSummarizer;
+use futures::StreamExt;
+
+/// Serves chat completions as server-sent events on a local port, ending
+/// the stream with `[DONE]` only when `complete` is set.
+fn serve_stream(complete: bool) -> String {
+    serve(move |_| {
+        let mut body = String::new();
+        for piece in ["Partial ", "summary"] {
+            body.push_str(&format!("data: {{\"choices\":[{{\"delta\":{{\"content\":\"{}\"}}}}]}}\n\n", piece));
+        }
+        if complete {
+            body.push_str("data: [DONE]\n\n");
+        }
+        Response::new("200 OK", "text/event-stream", body)
+    })
+}
+
+async fn stream(complete: bool) -> Vec<anyhow::Result<String>> {
+    let config = Config::default();
+    let options = ProviderOptions::from_config(&config).unwrap();
+    let provider = OpenAiProvider::new("key".to_string(), serve_stream(complete), "gpt-4".to_string(), options);
+    let summarizer = LlmSummarizer::new(Box::new(provider), &config, None).unwrap();
+
+    summarizer.summarize_stream("Some text.", None).await.unwrap().stream.collect().await
+}
+
+#[tokio::test]
+async fn stream_without_done_is_an_error() {
+    let pieces = stream(false).await;
+
+    let (last, text) = pieces.split_last().unwrap();
+    assert_eq!(text.iter().map(|piece| piece.as_ref().unwrap().as_str()).collect::<String>(), "Partial summary");
+    assert!(last.as_ref().unwrap_err().is::<IncompleteStream>());
+}
+
+#[tokio::test]
+async fn complete_stream_ends_cleanly() {
+    let pieces = stream(true).await;
+
+    let text: String = pieces.into_iter().map(Result::unwrap).collect();
+    assert_eq!(text, "Partial summary");
+}
+
+/// Streams `streamed` and answers requests that don't stream with a
+/// longer, detailed summary.
+fn serve_streamed_then_detailed(streamed: &'static str) -> String {
+    serve(move |request| {
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        if body["stream"] == true {
+            let body = format!("data: {{\"choices\":[{{\"delta\":{{\"content\":\"{}\"}}}}]}}\n\ndata: [DONE]\n\n", streamed);
+            Response::new("200 OK", "text/event-stream", body)
+        } else {
+            Response::completion("A detailed summary of the configuration options and their defaults.")
+        }
+    })
+}
+
+async fn stream_and_finish(config: &Config, content: &str, streamed: &'static str) -> GeneratedSummary {
+    stream_and_finish_cached(config, content, streamed, None).await
+}
+
+async fn stream_and_finish_cached(config: &Config, content: &str, streamed: &'static str, cache: Option<SummaryCache>) -> GeneratedSummary {
+    let options = ProviderOptions::from_config(config).unwrap();
+    let provider = OpenAiProvider::new("key".to_string(), serve_streamed_then_detailed(streamed), "gpt-4".to_string(), options);
+    let summarizer = LlmSummarizer::new(Box::new(provider), config, cache).unwrap();
+
+    let summary = summarizer.summarize_stream(content, None).await.unwrap();
+    let text: String = summary.stream.map(Result::unwrap).collect().await;
+    let streamed = GeneratedSummary {
+        text,
+        chunks: summary.chunks,
+        model: summary.model,
+        usage: summary.usage,
+        retried: false,
+        max_tokens: summary.max_tokens,
+    };
+    summarizer.finish_stream(&summary.input, None, streamed).await.unwrap()
+}
+
+#[tokio::test]
+async fn streamed_summaries_get_the_length_checks() {
+    let content = "Configuration options and their defaults. ".repeat(20);
+    let mut config = Config::default();
+    config.api.max_retries = 0;
+    config.default.min_tokens = 8;
+
+    let expanded = stream_and_finish(&config, &content, "Brief.").await;
+    assert_eq!(expanded.text, "A detailed summary of the configuration options and their defaults.");
+
+    config.default.max_summary_chars = Some(20);
+    let capped = stream_and_finish(&config, &content, "Brief.").await;
+    assert_eq!(capped.text, "A detailed summary");
+}
+
+#[tokio::test]
+async fn streamed_refusals_are_requested_again() {
+    let mut config = Config::default();
+    config.api.max_retries = 1;
+    config.default.self_check = true;
+
+    let summary = stream_and_finish(&config, "Configuration options and their defaults.", "I'm sorry, I can't help with that.").await;
+    assert_eq!(summary.text, "A detailed summary of the configuration options and their defaults.");
+    assert!(summary.retried);
+}
+
+#[tokio::test]
+async fn streamed_summaries_are_cached_once_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = SummaryCache::new(dir.path().to_path_buf(), "gpt-4".to_string());
+    let content = "Configuration options and their defaults.";
+    let mut config = Config::default();
+    config.api.max_retries = 1;
+    config.default.self_check = true;
+
+    stream_and_finish_cached(&config, content, "I'm sorry, I can't help with that.", Some(cache.clone())).await;
+
+    config.default.self_check = false;
+    let summarizer = LlmSummarizer::new(Box::new(MockProvider::canned("Fresh.")), &config, Some(cache)).unwrap();
+    let summary = summarizer.summarize(content, None).await.unwrap();
+    assert_eq!(summary.text, "A detailed summary of the configuration options and their defaults.");
+}
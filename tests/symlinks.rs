@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+use doctldr::config::Config;
+use doctldr::DocumentProcessor;
+
+async fn processed_paths(dir: &Path, follow_symlinks: bool) -> Vec<String> {
+    let mut config = Config::default();
+    config.processing.follow_symlinks = follow_symlinks;
+    let processor = DocumentProcessor::new(&config.processing, false);
+    let mut paths: Vec<String> = processor
+        .process_directory(dir)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|document| document.relative_path.unwrap().display().to_string())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[tokio::test]
+async fn symlinks_are_followed_only_when_enabled() {
+    let outside = tempfile::tempdir().unwrap();
+    std::fs::write(outside.path().join("shared.md"), "# Shared").unwrap();
+    std::fs::create_dir(outside.path().join("vendor")).unwrap();
+    std::fs::write(outside.path().join("vendor/notes.md"), "# Notes").unwrap();
+
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::write(docs.path().join("guide.md"), "# Guide").unwrap();
+    symlink(outside.path().join("shared.md"), docs.path().join("shared.md")).unwrap();
+    symlink(outside.path().join("vendor"), docs.path().join("vendor")).unwrap();
+
+    assert_eq!(processed_paths(docs.path(), false).await, ["guide.md"]);
+    assert_eq!(processed_paths(docs.path(), true).await, ["guide.md", "shared.md", "vendor/notes.md"]);
+}
+
+#[tokio::test]
+async fn symlink_cycles_do_not_stop_the_walk() {
+    let docs = tempfile::tempdir().unwrap();
+    std::fs::create_dir(docs.path().join("nested")).unwrap();
+    std::fs::write(docs.path().join("nested/guide.md"), "# Guide").unwrap();
+    symlink(docs.path(), docs.path().join("nested/loop")).unwrap();
+    symlink(docs.path().join("missing.md"), docs.path().join("dangling.md")).unwrap();
+
+    assert_eq!(processed_paths(docs.path(), true).await, ["nested/guide.md"]);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::summary;
+use doctldr::OutputWriter;
+
+#[tokio::test]
+async fn xml_output_parses() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("summary.xml");
+    let mut summaries = vec![
+        summary("docs/a & b.md", "Use `<T>` generics.\n\n<div>raw</div> and ]]> too"),
+        summary("docs/intro.md", "Intro"),
+    ];
+    summaries[1].metadata.language = Some("eng".to_string());
+    OutputWriter::new("xml").unwrap().write(summaries, Some(&out)).await.unwrap();
+
+    let xml = std::fs::read_to_string(&out).unwrap();
+    let document = roxmltree::Document::parse(&xml).unwrap();
+    let root = document.root_element();
+    assert_eq!(root.tag_name().name(), "summaries");
+
+    let summaries: Vec<_> = root.children().filter(|node| node.has_tag_name("summary")).collect();
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children().find(|child| child.has_tag_name(name)).and_then(|child| child.text()).map(str::to_string)
+    };
+    let paths: Vec<_> = summaries.iter().map(|&node| child_text(node, "path").unwrap()).collect();
+    assert_eq!(paths, ["docs/a & b.md", "docs/intro.md"]);
+
+    let text: String = summaries[0]
+        .children()
+        .find(|child| child.has_tag_name("text"))
+        .unwrap()
+        .descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect();
+    assert_eq!(text, "Use `<T>` generics.\n\n<div>raw</div> and ]]> too");
+
+    let metadata = summaries[1].children().find(|child| child.has_tag_name("metadata")).unwrap();
+    assert_eq!(child_text(metadata, "language").as_deref(), Some("eng"));
+}